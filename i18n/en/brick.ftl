@@ -10,16 +10,105 @@ cli-verbose-long-help =
     Each occurrance increases the level from info to debug to trace.
 
 cli-info-about = Display info on an archive
+cli-info-input-help = archive to display info on, - reads from stdin
+cli-info-output-format-help = Specify the output format
+cli-info-output-format-long-help =
+    Specify the output format. The default is text.
+    json prints a single document, ndjson prints one entry per line followed by the summary
+    and csv prints one row per entry.
 
 cli-pack-about = Pack files and directories into an archive
 cli-pack-format-help = Specify the compression format
-cli-pack-compression-help = Specify the compresion level [possible values: auto, 0-9]
+cli-pack-compression-help = Specify the compresion level [possible values: auto or a level of the format]
 cli-pack-compression-long-help =
-    Specify the compression level from 0..9.
+    Specify the compression level of the preceding format.
     Default if is auto. 
     auto means compression level is decided by the format.
+    gzip, zip, xz, lzma and lzip support 0..9, bzip2 supports 1..9,
+    zstd supports 1..22 and negative levels for faster compression.
+cli-pack-long-help = Enable zstd long distance matching with a window of 2^WINDOW_LOG bytes
+cli-pack-long-long-help =
+    Enable zstd long distance matching with a window of 2^WINDOW_LOG bytes.
+    Without a value the window is 2^27 bytes (128 MiB).
+    Improves the compression of large inputs with matches far apart.
+cli-pack-long-invalid = window log must be a number from { $min } to { $max }
 cli-pack-compression-value-name = level
-cli-pack-input-help = files and directories to pack
-cli-pack-output-help = output file
+cli-pack-parents-help = Create missing parent directories of the output file
+cli-pack-overwrite-help = Replace an existing output file
+cli-pack-no-clobber-help = Never replace an existing output file
+cli-pack-no-clobber-long-help =
+    Never replace an existing output file.
+    Without --overwrite or --no-clobber brick asks before replacing a file
+    if stdin is a terminal and refuses otherwise.
+    Refusing to overwrite exits with code 3.
+cli-pack-follow-symlinks-help = Add the files symlinks point to instead of the links
+cli-pack-one-file-system-help = Do not descend into directories on other file systems
+cli-pack-max-depth-help = Only descend this many levels into input directories
+cli-pack-special-files-help = Add fifos, sockets and device files, these are skipped by default
+cli-pack-exclude-help = Leave out files and directories matching the pattern
+cli-pack-exclude-long-help =
+    Leave out files and directories matching the pattern. Patterns use the syntax of .gitignore files,
+    patterns without a / match the name at any depth, others the path below the input directory.
+    Later --exclude and --include patterns override earlier ones
+cli-pack-include-help = Add files and directories matching the pattern even if they were excluded
+cli-pack-exclude-vcs-help = Leave out the metadata of version control systems like .git
+cli-pack-exclude-from-help = Leave out files and directories matching the patterns in the file
+cli-pack-gitignore-help = Leave out files and directories ignored by .gitignore files
+cli-pack-no-brickignore-help = Do not read exclude patterns from .brickignore files
+cli-pack-prefix-help = Place all entries in this directory of the archive
+cli-pack-reproducible-help = Write the same bytes for the same files regardless of owner and time
+cli-pack-reproducible-long-help =
+    Write the same bytes for the same files regardless of owner and time.
+    Entries are added sorted by name with uid and gid 0, no user and group names and permissions
+    of 0755 for directories and executables and 0644 for other files. Modification times are
+    clamped to SOURCE_DATE_EPOCH, or set to the unix epoch if it is not set, and gzip headers
+    store no time.
+cli-pack-keep-help = Keep the input file after compressing it
+cli-pack-delete-input-help = Delete the input file after compressing it, this is the default
+cli-pack-delete-input-long-help =
+    Delete the input file after compressing it like gzip does, this is the default.
+    Only possible when compressing a single file without a container format, files packed into a
+    container are always kept.
+cli-pack-files-from-help = Read the paths to pack from a file with one path per line, - reads from stdin
+cli-pack-null-help = Paths in the --files-from list are separated by NUL bytes like find -print0 writes them
+cli-pack-input-help = files and directories to pack, - compresses stdin
+cli-pack-output-help = output file, - writes to stdout
 
-cli-unpack-about = Unpack an archive
\ No newline at end of file
+cli-limits-max-size-help = Abort once the archive decompresses to more bytes, defaults to 64G
+cli-limits-max-entries-help = Abort on archives with more entries, defaults to 1000000
+cli-limits-max-entry-size-help = Abort on entries larger than this, defaults to 16G
+cli-limits-max-ratio-help = Abort on compression ratios higher than this, defaults to 1000
+cli-limits-max-layers-help = Abort on archives nested in more layers, defaults to 8
+cli-limits-no-limits-help = Disable all limits, only use this for archives you trust
+cli-rename-strip-components-help = Remove this many leading components from entry names
+cli-rename-transform-help = Rewrite entry names with a sed expression s/regex/replacement/flags
+cli-rename-transform-long-help =
+    Rewrite entry names with a sed expression s/regex/replacement/flags. The regex uses extended syntax
+    like sed -E, \1 to \9 and & in the replacement refer to the groups and the whole match and the
+    flags g and i replace all matches and ignore case. Multiple transforms are applied in order
+cli-unpack-about = Unpack an archive
+cli-unpack-input-help = archive to unpack, - reads from stdin
+cli-unpack-output-help = directory to unpack the archive into, - writes a single compressed file to stdout
+cli-unpack-output-value-name = directory
+cli-unpack-keep-help = Keep the archive, this is the default
+cli-unpack-overwrite-help = Replace existing files, this is the default
+cli-unpack-skip-existing-help = Keep existing files and skip their entries
+cli-unpack-keep-newer-help = Only replace existing files which are older than their entries
+cli-unpack-rename-help = Unpack entries next to existing files with .1, .2, ... appended
+cli-unpack-backup-help = Move existing files to a backup with ~ appended before replacing them
+cli-unpack-unsafe-paths-help = Allow entries and links outside of the output directory
+cli-unpack-unsafe-paths-long-help =
+    Allow entries with absolute paths or .. components and links pointing outside
+    of the output directory. Only use this for archives you trust.
+cli-unpack-preserve-permissions-help = Restore setuid, setgid and sticky bits of entries, these are cleared by default
+cli-unpack-delete-input-help = Delete the archive after unpacking it
+cli-unpack-delete-input-long-help =
+    Delete the archive after unpacking it like gzip -d does.
+    Only possible for a single compressed file without a container format.
+pack-overwrite-prompt = Overwrite '{ $path }'? [y/N]
+info-format = Format: { $format }
+info-layers = Layers: { $layers }
+info-entries = Entries: { $count }
+info-uncompressed-size = Uncompressed size: { $size } bytes
+info-archive-size = Archive size: { $size } bytes
+info-compression-ratio = Compression ratio: { $ratio }
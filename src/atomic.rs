@@ -0,0 +1,201 @@
+//! Write output files atomically
+//!
+//! Data is written to a temporary file next to the destination which is renamed into place
+//! once it is complete. An existing file is never truncated and a failed write leaves no
+//! partial file behind.
+
+use std::{
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, ErrorKind, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, MutexGuard,
+    },
+};
+
+use log::debug;
+use once_cell::sync::Lazy;
+
+use crate::error::{Error, Result};
+
+/// Temporary files which are still being written
+static PENDING: Lazy<Mutex<Vec<PathBuf>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Counter making the names of temporary files unique within this process
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Remove all temporary files which are still being written
+///
+/// Meant to be called from a signal handler before exiting.
+pub fn remove_pending() {
+    for path in lock_pending().iter() {
+        debug!("Removing incomplete output {}", path.display());
+
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// File which only appears at its destination after [AtomicFile::commit]
+pub struct AtomicFile {
+    file: File,
+    temp_path: PathBuf,
+    path: PathBuf,
+}
+
+impl AtomicFile {
+    /// Create a temporary file in the directory of `path`
+    pub fn create(path: &Path) -> Result<Self> {
+        let map_err = |source| Error::CreateArchiveFile {
+            source,
+            path: path.display().to_string(),
+        };
+
+        let directory = path
+            .parent()
+            .filter(|directory| !directory.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."));
+        let name = path
+            .file_name()
+            .map(ToOwned::to_owned)
+            .unwrap_or_else(|| OsString::from("archive"));
+
+        loop {
+            let mut temp_name = OsString::from(".");
+            temp_name.push(&name);
+            temp_name.push(format!(
+                ".{}-{}.tmp",
+                process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            let temp_path = directory.join(temp_name);
+
+            // `create_new` keeps the default permissions of `File::create`
+            match OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .open(&temp_path)
+            {
+                Ok(file) => {
+                    debug!("Writing {} to {}", path.display(), temp_path.display());
+
+                    lock_pending().push(temp_path.clone());
+
+                    return Ok(Self {
+                        file,
+                        temp_path,
+                        path: path.to_owned(),
+                    });
+                }
+                Err(error) if error.kind() == ErrorKind::AlreadyExists => continue,
+                Err(error) => return Err(map_err(error)),
+            }
+        }
+    }
+
+    /// The temporary file
+    pub fn as_file(&self) -> &File {
+        &self.file
+    }
+
+    /// Move the complete file to its destination
+    pub fn commit(self) -> Result<()> {
+        debug!(
+            "Moving {} to {}",
+            self.temp_path.display(),
+            self.path.display()
+        );
+
+        let map_err = |source| Error::CreateArchiveFile {
+            source,
+            path: self.path.display().to_string(),
+        };
+
+        self.file.sync_all().map_err(map_err)?;
+        fs::rename(&self.temp_path, &self.path).map_err(map_err)?;
+
+        unregister(&self.temp_path);
+
+        Ok(())
+    }
+}
+
+/// An uncommitted file is removed
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        let mut pending = lock_pending();
+
+        if let Some(index) = pending.iter().position(|path| *path == self.temp_path) {
+            pending.remove(index);
+
+            debug!("Removing incomplete output {}", self.temp_path.display());
+
+            let _ = fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Lock the pending files, a panic while holding the lock does not matter for removing them
+fn lock_pending() -> MutexGuard<'static, Vec<PathBuf>> {
+    match PENDING.lock() {
+        Ok(pending) => pending,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+fn unregister(temp_path: &Path) {
+    lock_pending().retain(|path| path != temp_path);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    /// test the destination only changes on commit
+    #[test]
+    fn commit_and_abort() -> Result<(), Box<dyn Error>> {
+        // setup working directory
+
+        let working_directory = tempdir()?;
+        let path = working_directory.path().join("archive.tar");
+        fs::write(&path, "old")?;
+
+        // abort a write
+
+        let mut file = AtomicFile::create(&path)?;
+        file.write_all(b"partial")?;
+        drop(file);
+
+        assert_eq!(fs::read_to_string(&path)?, "old");
+        assert_eq!(fs::read_dir(&working_directory)?.count(), 1);
+
+        // commit a write
+
+        let mut file = AtomicFile::create(&path)?;
+        file.write_all(b"new")?;
+        file.commit()?;
+
+        assert_eq!(fs::read_to_string(&path)?, "new");
+        assert_eq!(fs::read_dir(&working_directory)?.count(), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+//! Validate chains of formats before packing
+//!
+//! A chain is ordered from the inner most to the outer most format. Only the first format
+//! may be a container, every other format is a codec compressing the output of the previous
+//! one. Without a container the chain compresses a single file.
+
+use std::path::Path;
+
+use crate::{
+    error::{Error, Result},
+    ArchiveFormat, FormatKind,
+};
+
+/// Check that `formats` can pack `input_paths`
+pub fn validate<P>(formats: &[ArchiveFormat], input_paths: &[P]) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    let (inner, outer) = formats
+        .split_first()
+        .ok_or(Error::MissingCompressionFormat)?;
+
+    if let Some(container) = outer.iter().find(|format| format.is_container()) {
+        return Err(Error::ContainerNotInnermost(*container));
+    }
+
+    match (inner.kind(), input_paths) {
+        (FormatKind::Container, _) => Ok(()),
+        (FormatKind::Codec, [path]) if path.as_ref().is_dir() => Err(Error::CodecDirectory {
+            format: *inner,
+            path: path.as_ref().display().to_string(),
+        }),
+        (FormatKind::Codec, [_]) => Ok(()),
+        (FormatKind::Codec, paths) => Err(Error::CodecMultipleInputs {
+            format: *inner,
+            count: paths.len(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    /// test valid and invalid chains
+    #[test]
+    fn validate_chains() {
+        use ArchiveFormat::*;
+
+        let one = ["file.txt"];
+        let many = ["a", "b", "c"];
+
+        let valid: [(&[ArchiveFormat], &[&str]); 5] = [
+            (&[Tar], &many),
+            (&[Tar, GZip, Xz], &many),
+            (&[Zip, GZip], &many),
+            (&[GZip], &one),
+            (&[Zstd, Xz], &one),
+        ];
+
+        for (formats, inputs) in valid {
+            assert!(validate(formats, inputs).is_ok(), "{formats:?}");
+        }
+
+        assert!(matches!(
+            validate::<&str>(&[], &one),
+            Err(Error::MissingCompressionFormat)
+        ));
+        assert!(matches!(
+            validate(&[GZip, Tar], &one),
+            Err(Error::ContainerNotInnermost(Tar))
+        ));
+        assert!(matches!(
+            validate(&[Tar, Zip], &many),
+            Err(Error::ContainerNotInnermost(Zip))
+        ));
+        assert!(matches!(
+            validate(&[GZip], &many),
+            Err(Error::CodecMultipleInputs {
+                format: GZip,
+                count: 3
+            })
+        ));
+        assert!(matches!(
+            validate(&[GZip, Zip], &many),
+            Err(Error::ContainerNotInnermost(Zip))
+        ));
+    }
+
+    /// test directories can only be packed by containers
+    #[test]
+    fn validate_directory() -> Result<(), Box<dyn std::error::Error>> {
+        let directory = tempdir()?;
+        let inputs = [directory.path()];
+
+        assert!(validate(&[ArchiveFormat::Tar, ArchiveFormat::Xz], &inputs).is_ok());
+        assert!(matches!(
+            validate(&[ArchiveFormat::Xz], &inputs),
+            Err(Error::CodecDirectory {
+                format: ArchiveFormat::Xz,
+                ..
+            })
+        ));
+
+        Ok(())
+    }
+}
@@ -1,16 +1,59 @@
-use brick::ArchiveFormat;
+use brick::{
+    extract,
+    packer::zstd::{DEFAULT_LONG_WINDOW_LOG, MAX_WINDOW_LOG, MIN_WINDOW_LOG},
+    rename::Transform,
+    ArchiveFormat,
+};
 use clap::{command, Arg, ArgGroup, Command};
 use once_cell::sync::Lazy;
 
-use crate::macros::fl;
+use crate::{macros::fl, output::OutputFormat};
 
 pub mod args {
+    pub static BACKUP: &str = "backup";
     pub static COMPRESSION_LEVEL: &str = "compression level";
+    pub static CONFLICT_GROUP: &str = "conflict group";
+    pub static DELETE_INPUT: &str = "delete input";
+    pub static EXCLUDE: &str = "exclude";
+    pub static EXCLUDE_FROM: &str = "exclude from";
+    pub static EXCLUDE_VCS: &str = "exclude vcs";
+    pub static FILES_FROM: &str = "files from";
+    pub static FOLLOW_SYMLINKS: &str = "follow symlinks";
     pub static FORMAT: &str = "format";
     pub static FORMAT_GROUP: &str = "format group";
+    pub static GITIGNORE: &str = "gitignore";
+    pub static INCLUDE: &str = "include";
+    pub static INPUT_PATH: &str = "input path";
+    pub static LONG_WINDOW: &str = "long window";
     pub static INPUT_PATHS: &str = "input paths";
+    pub static KEEP: &str = "keep";
+    pub static KEEP_NEWER: &str = "keep newer";
+    pub static OUTPUT_DIRECTORY: &str = "output directory";
+    pub static OUTPUT_FORMAT: &str = "output format";
+    pub static NO_BRICKIGNORE: &str = "no brickignore";
+    pub static NO_CLOBBER: &str = "no clobber";
+    pub static NULL: &str = "null";
+    pub static ONE_FILE_SYSTEM: &str = "one file system";
     pub static OUTPUT_PATH: &str = "output path";
+    pub static OVERWRITE: &str = "overwrite";
+    pub static PARENTS: &str = "parents";
+    pub static PREFIX: &str = "prefix";
+    pub static PRESERVE_PERMISSIONS: &str = "preserve permissions";
+    pub static RECURSION_DEPTH: &str = "recursion depth";
+    pub static RENAME: &str = "rename";
+    pub static REPRODUCIBLE: &str = "reproducible";
+    pub static SKIP_EXISTING: &str = "skip existing";
+    pub static SPECIAL_FILES: &str = "special files";
+    pub static STRIP_COMPONENTS: &str = "strip components";
+    pub static TRANSFORM: &str = "transform";
+    pub static UNSAFE_PATHS: &str = "unsafe paths";
     pub static LOG_LEVEL_GROUP: &str = "log level group";
+    pub static MAX_LAYERS: &str = "max layers";
+    pub static MAX_ENTRIES: &str = "max entries";
+    pub static MAX_ENTRY_SIZE: &str = "max entry size";
+    pub static MAX_RATIO: &str = "max ratio";
+    pub static MAX_SIZE: &str = "max size";
+    pub static NO_LIMITS: &str = "no limits";
 }
 
 pub fn app() -> Command<'static> {
@@ -19,8 +62,6 @@ pub fn app() -> Command<'static> {
     static QUIET_LONG_HELP: Lazy<String> = Lazy::new(|| fl!("cli-quiet-long-help"));
     static VERBOSE_HELP: Lazy<String> = Lazy::new(|| fl!("cli-verbose-help"));
     static VERBOSE_LONG_HELP: Lazy<String> = Lazy::new(|| fl!("cli-verbose-long-help"));
-    static INFO_ABOUT: Lazy<String> = Lazy::new(|| fl!("cli-info-about"));
-    static UNPACK_ABOUT: Lazy<String> = Lazy::new(|| fl!("cli-unpack-about"));
 
     command!()
         .about(ABOUT.as_str())
@@ -44,17 +85,115 @@ pub fn app() -> Command<'static> {
                 .max_occurrences(2)
                 .multiple_occurrences(true),
         ])
-        .subcommands(vec![
-            Command::new("info")
-                .visible_alias("i")
-                .about(INFO_ABOUT.as_str())
-                .arg_required_else_help(true),
-            pack(),
-            Command::new("unpack")
-                .visible_alias("u")
-                .about(UNPACK_ABOUT.as_str())
-                .arg_required_else_help(true),
+        .subcommands(vec![info(), pack(), unpack()])
+}
+
+fn info() -> Command<'static> {
+    static ABOUT: Lazy<String> = Lazy::new(|| fl!("cli-info-about"));
+    static INPUT_HELP: Lazy<String> = Lazy::new(|| fl!("cli-info-input-help"));
+    static OUTPUT_FORMAT_HELP: Lazy<String> = Lazy::new(|| fl!("cli-info-output-format-help"));
+    static OUTPUT_FORMAT_LONG_HELP: Lazy<String> =
+        Lazy::new(|| fl!("cli-info-output-format-long-help"));
+
+    Command::new("info")
+        .visible_alias("i")
+        .about(ABOUT.as_str())
+        .arg_required_else_help(true)
+        .args(&[
+            Arg::new(args::INPUT_PATH)
+                .help(INPUT_HELP.as_str())
+                .required(true)
+                .takes_value(true)
+                .value_name("ARCHIVE"),
+            Arg::new(args::OUTPUT_FORMAT)
+                .long("output-format")
+                .help(OUTPUT_FORMAT_HELP.as_str())
+                .long_help(OUTPUT_FORMAT_LONG_HELP.as_str())
+                .takes_value(true)
+                .default_value("text")
+                .possible_values(OutputFormat::all())
+                .ignore_case(true),
         ])
+        .args(limits())
+}
+
+/// Limits protecting against decompression bombs, shared by info and unpack
+fn limits() -> [Arg<'static>; 6] {
+    static MAX_SIZE_HELP: Lazy<String> = Lazy::new(|| fl!("cli-limits-max-size-help"));
+    static MAX_ENTRIES_HELP: Lazy<String> = Lazy::new(|| fl!("cli-limits-max-entries-help"));
+    static MAX_ENTRY_SIZE_HELP: Lazy<String> = Lazy::new(|| fl!("cli-limits-max-entry-size-help"));
+    static MAX_RATIO_HELP: Lazy<String> = Lazy::new(|| fl!("cli-limits-max-ratio-help"));
+    static MAX_LAYERS_HELP: Lazy<String> = Lazy::new(|| fl!("cli-limits-max-layers-help"));
+    static NO_LIMITS_HELP: Lazy<String> = Lazy::new(|| fl!("cli-limits-no-limits-help"));
+
+    [
+        Arg::new(args::MAX_SIZE)
+            .long("max-size")
+            .help(MAX_SIZE_HELP.as_str())
+            .value_name("BYTES")
+            .takes_value(true)
+            .validator(extract::parse_size),
+        Arg::new(args::MAX_ENTRIES)
+            .long("max-entries")
+            .help(MAX_ENTRIES_HELP.as_str())
+            .value_name("COUNT")
+            .takes_value(true)
+            .validator(|value| value.parse::<u64>()),
+        Arg::new(args::MAX_ENTRY_SIZE)
+            .long("max-entry-size")
+            .help(MAX_ENTRY_SIZE_HELP.as_str())
+            .value_name("BYTES")
+            .takes_value(true)
+            .validator(extract::parse_size),
+        Arg::new(args::MAX_RATIO)
+            .long("max-ratio")
+            .help(MAX_RATIO_HELP.as_str())
+            .value_name("RATIO")
+            .takes_value(true)
+            .validator(|value| value.parse::<u64>()),
+        Arg::new(args::MAX_LAYERS)
+            .long("max-layers")
+            .help(MAX_LAYERS_HELP.as_str())
+            .value_name("LAYERS")
+            .takes_value(true)
+            .validator(|value| value.parse::<u64>()),
+        Arg::new(args::NO_LIMITS)
+            .long("no-limits")
+            .help(NO_LIMITS_HELP.as_str())
+            .conflicts_with_all(&[
+                args::MAX_SIZE,
+                args::MAX_ENTRIES,
+                args::MAX_ENTRY_SIZE,
+                args::MAX_RATIO,
+                args::MAX_LAYERS,
+            ]),
+    ]
+}
+
+/// Rewriting of entry names, shared by pack and unpack
+fn rename() -> [Arg<'static>; 2] {
+    static STRIP_COMPONENTS_HELP: Lazy<String> =
+        Lazy::new(|| fl!("cli-rename-strip-components-help"));
+    static TRANSFORM_HELP: Lazy<String> = Lazy::new(|| fl!("cli-rename-transform-help"));
+    static TRANSFORM_LONG_HELP: Lazy<String> = Lazy::new(|| fl!("cli-rename-transform-long-help"));
+
+    [
+        Arg::new(args::STRIP_COMPONENTS)
+            .long("strip-components")
+            .help(STRIP_COMPONENTS_HELP.as_str())
+            .value_name("COUNT")
+            .takes_value(true)
+            .validator(|value| value.parse::<usize>()),
+        Arg::new(args::TRANSFORM)
+            .long("transform")
+            .help(TRANSFORM_HELP.as_str())
+            .long_help(TRANSFORM_LONG_HELP.as_str())
+            .value_name("EXPRESSION")
+            .takes_value(true)
+            .multiple_occurrences(true)
+            .number_of_values(1)
+            .validator(|value| value.parse::<Transform>()),
+    ]
 }
 
 fn pack() -> Command<'static> {
@@ -65,6 +204,34 @@ fn pack() -> Command<'static> {
         Lazy::new(|| fl!("cli-pack-compression-long-help"));
     static COMPRESSION_VALUE_NAME: Lazy<String> =
         Lazy::new(|| fl!("cli-pack-compression-value-name"));
+    static LONG_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-long-help"));
+    static LONG_LONG_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-long-long-help"));
+    static LONG_DEFAULT: Lazy<String> = Lazy::new(|| DEFAULT_LONG_WINDOW_LOG.to_string());
+    static PARENTS_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-parents-help"));
+    static OVERWRITE_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-overwrite-help"));
+    static NO_CLOBBER_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-no-clobber-help"));
+    static NO_CLOBBER_LONG_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-no-clobber-long-help"));
+    static FOLLOW_SYMLINKS_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-follow-symlinks-help"));
+    static ONE_FILE_SYSTEM_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-one-file-system-help"));
+    static MAX_DEPTH_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-max-depth-help"));
+    static SPECIAL_FILES_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-special-files-help"));
+    static EXCLUDE_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-exclude-help"));
+    static EXCLUDE_LONG_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-exclude-long-help"));
+    static INCLUDE_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-include-help"));
+    static EXCLUDE_VCS_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-exclude-vcs-help"));
+    static EXCLUDE_FROM_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-exclude-from-help"));
+    static GITIGNORE_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-gitignore-help"));
+    static NO_BRICKIGNORE_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-no-brickignore-help"));
+    static KEEP_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-keep-help"));
+    static DELETE_INPUT_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-delete-input-help"));
+    static DELETE_INPUT_LONG_HELP: Lazy<String> =
+        Lazy::new(|| fl!("cli-pack-delete-input-long-help"));
+    static PREFIX_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-prefix-help"));
+    static REPRODUCIBLE_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-reproducible-help"));
+    static REPRODUCIBLE_LONG_HELP: Lazy<String> =
+        Lazy::new(|| fl!("cli-pack-reproducible-long-help"));
+    static FILES_FROM_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-files-from-help"));
+    static NULL_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-null-help"));
     static INPUT_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-input-help"));
     static OUTPUT_HELP: Lazy<String> = Lazy::new(|| fl!("cli-pack-output-help"));
 
@@ -95,12 +262,126 @@ fn pack() -> Command<'static> {
                 .multiple_occurrences(true)
                 .takes_value(true)
                 .default_value("auto")
-                .hide_possible_values(true)
-                .possible_values(["auto", "0", "1", "2", "3", "4", "5", "6", "7", "8", "9"])
-                .ignore_case(true),
+                .allow_hyphen_values(true),
+            Arg::new(args::LONG_WINDOW)
+                .long("long")
+                .help(LONG_HELP.as_str())
+                .long_help(LONG_LONG_HELP.as_str())
+                .value_name("WINDOW_LOG")
+                .takes_value(true)
+                .min_values(0)
+                .max_values(1)
+                .require_equals(true)
+                .default_missing_value(LONG_DEFAULT.as_str())
+                .validator(|value| {
+                    value
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|window_log| (MIN_WINDOW_LOG..=MAX_WINDOW_LOG).contains(window_log))
+                        .map(|_| ())
+                        .ok_or_else(|| {
+                            fl!(
+                                "cli-pack-long-invalid",
+                                min = MIN_WINDOW_LOG,
+                                max = MAX_WINDOW_LOG
+                            )
+                        })
+                }),
+            Arg::new(args::PARENTS)
+                .short('p')
+                .long("parents")
+                .help(PARENTS_HELP.as_str()),
+            Arg::new(args::OVERWRITE)
+                .long("overwrite")
+                .help(OVERWRITE_HELP.as_str())
+                .conflicts_with(args::NO_CLOBBER),
+            Arg::new(args::NO_CLOBBER)
+                .short('n')
+                .long("no-clobber")
+                .help(NO_CLOBBER_HELP.as_str())
+                .long_help(NO_CLOBBER_LONG_HELP.as_str()),
+            Arg::new(args::FOLLOW_SYMLINKS)
+                .short('L')
+                .long("follow-symlinks")
+                .help(FOLLOW_SYMLINKS_HELP.as_str()),
+            Arg::new(args::ONE_FILE_SYSTEM)
+                .long("one-file-system")
+                .help(ONE_FILE_SYSTEM_HELP.as_str()),
+            Arg::new(args::RECURSION_DEPTH)
+                .long("max-depth")
+                .help(MAX_DEPTH_HELP.as_str())
+                .value_name("LEVELS")
+                .takes_value(true)
+                .validator(|value| value.parse::<usize>()),
+            Arg::new(args::SPECIAL_FILES)
+                .long("special-files")
+                .help(SPECIAL_FILES_HELP.as_str()),
+            Arg::new(args::EXCLUDE)
+                .long("exclude")
+                .help(EXCLUDE_HELP.as_str())
+                .long_help(EXCLUDE_LONG_HELP.as_str())
+                .value_name("PATTERN")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .number_of_values(1),
+            Arg::new(args::INCLUDE)
+                .long("include")
+                .help(INCLUDE_HELP.as_str())
+                .value_name("PATTERN")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .number_of_values(1),
+            Arg::new(args::EXCLUDE_VCS)
+                .long("exclude-vcs")
+                .help(EXCLUDE_VCS_HELP.as_str()),
+            Arg::new(args::EXCLUDE_FROM)
+                .long("exclude-from")
+                .help(EXCLUDE_FROM_HELP.as_str())
+                .value_name("FILE")
+                .takes_value(true)
+                .allow_invalid_utf8(true)
+                .multiple_occurrences(true)
+                .number_of_values(1),
+            Arg::new(args::GITIGNORE)
+                .long("gitignore")
+                .help(GITIGNORE_HELP.as_str()),
+            Arg::new(args::NO_BRICKIGNORE)
+                .long("no-brickignore")
+                .help(NO_BRICKIGNORE_HELP.as_str()),
+            Arg::new(args::PREFIX)
+                .long("prefix")
+                .help(PREFIX_HELP.as_str())
+                .value_name("DIRECTORY")
+                .takes_value(true)
+                .allow_invalid_utf8(true),
+            Arg::new(args::REPRODUCIBLE)
+                .long("reproducible")
+                .help(REPRODUCIBLE_HELP.as_str())
+                .long_help(REPRODUCIBLE_LONG_HELP.as_str()),
+            Arg::new(args::KEEP)
+                .short('k')
+                .long("keep")
+                .help(KEEP_HELP.as_str())
+                .overrides_with(args::DELETE_INPUT),
+            Arg::new(args::DELETE_INPUT)
+                .long("delete-input")
+                .help(DELETE_INPUT_HELP.as_str())
+                .long_help(DELETE_INPUT_LONG_HELP.as_str())
+                .overrides_with(args::KEEP),
+            Arg::new(args::FILES_FROM)
+                .short('T')
+                .long("files-from")
+                .help(FILES_FROM_HELP.as_str())
+                .value_name("FILE")
+                .takes_value(true)
+                .allow_invalid_utf8(true),
+            Arg::new(args::NULL)
+                .long("null")
+                .help(NULL_HELP.as_str())
+                .requires(args::FILES_FROM),
             Arg::new(args::INPUT_PATHS)
                 .help(INPUT_HELP.as_str())
-                .required(true)
+                .required_unless_present(args::FILES_FROM)
                 .takes_value(true)
                 .multiple_values(true)
                 .value_name("INPUT"),
@@ -109,6 +390,89 @@ fn pack() -> Command<'static> {
                 .last(true)
                 .required_unless_present_any(&[args::FORMAT_GROUP]),
         ])
+        .args(rename())
+}
+
+fn unpack() -> Command<'static> {
+    static ABOUT: Lazy<String> = Lazy::new(|| fl!("cli-unpack-about"));
+    static INPUT_HELP: Lazy<String> = Lazy::new(|| fl!("cli-unpack-input-help"));
+    static OUTPUT_HELP: Lazy<String> = Lazy::new(|| fl!("cli-unpack-output-help"));
+    static OUTPUT_VALUE_NAME: Lazy<String> = Lazy::new(|| fl!("cli-unpack-output-value-name"));
+    static KEEP_HELP: Lazy<String> = Lazy::new(|| fl!("cli-unpack-keep-help"));
+    static DELETE_INPUT_HELP: Lazy<String> = Lazy::new(|| fl!("cli-unpack-delete-input-help"));
+    static DELETE_INPUT_LONG_HELP: Lazy<String> =
+        Lazy::new(|| fl!("cli-unpack-delete-input-long-help"));
+    static OVERWRITE_HELP: Lazy<String> = Lazy::new(|| fl!("cli-unpack-overwrite-help"));
+    static SKIP_EXISTING_HELP: Lazy<String> = Lazy::new(|| fl!("cli-unpack-skip-existing-help"));
+    static KEEP_NEWER_HELP: Lazy<String> = Lazy::new(|| fl!("cli-unpack-keep-newer-help"));
+    static RENAME_HELP: Lazy<String> = Lazy::new(|| fl!("cli-unpack-rename-help"));
+    static BACKUP_HELP: Lazy<String> = Lazy::new(|| fl!("cli-unpack-backup-help"));
+    static UNSAFE_PATHS_HELP: Lazy<String> = Lazy::new(|| fl!("cli-unpack-unsafe-paths-help"));
+    static UNSAFE_PATHS_LONG_HELP: Lazy<String> =
+        Lazy::new(|| fl!("cli-unpack-unsafe-paths-long-help"));
+    static PRESERVE_PERMISSIONS_HELP: Lazy<String> =
+        Lazy::new(|| fl!("cli-unpack-preserve-permissions-help"));
+
+    Command::new("unpack")
+        .visible_alias("u")
+        .about(ABOUT.as_str())
+        .arg_required_else_help(true)
+        .group(ArgGroup::new(args::CONFLICT_GROUP).args(&[
+            args::OVERWRITE,
+            args::SKIP_EXISTING,
+            args::KEEP_NEWER,
+            args::RENAME,
+            args::BACKUP,
+        ]))
+        .args(&[
+            Arg::new(args::INPUT_PATH)
+                .help(INPUT_HELP.as_str())
+                .required(true)
+                .takes_value(true)
+                .value_name("ARCHIVE"),
+            Arg::new(args::OUTPUT_DIRECTORY)
+                .short('o')
+                .long("output")
+                .help(OUTPUT_HELP.as_str())
+                .value_name(OUTPUT_VALUE_NAME.as_str())
+                .takes_value(true)
+                .default_value("."),
+            Arg::new(args::KEEP)
+                .short('k')
+                .long("keep")
+                .help(KEEP_HELP.as_str())
+                .overrides_with(args::DELETE_INPUT),
+            Arg::new(args::DELETE_INPUT)
+                .long("delete-input")
+                .help(DELETE_INPUT_HELP.as_str())
+                .long_help(DELETE_INPUT_LONG_HELP.as_str())
+                .overrides_with(args::KEEP),
+            Arg::new(args::OVERWRITE)
+                .long("overwrite")
+                .help(OVERWRITE_HELP.as_str()),
+            Arg::new(args::SKIP_EXISTING)
+                .long("skip-existing")
+                .help(SKIP_EXISTING_HELP.as_str()),
+            Arg::new(args::KEEP_NEWER)
+                .long("keep-newer")
+                .help(KEEP_NEWER_HELP.as_str()),
+            Arg::new(args::RENAME)
+                .long("rename")
+                .help(RENAME_HELP.as_str()),
+            Arg::new(args::BACKUP)
+                .long("backup")
+                .help(BACKUP_HELP.as_str()),
+            Arg::new(args::UNSAFE_PATHS)
+                .long("unsafe-paths")
+                .help(UNSAFE_PATHS_HELP.as_str())
+                .long_help(UNSAFE_PATHS_LONG_HELP.as_str()),
+            Arg::new(args::PRESERVE_PERMISSIONS)
+                .short('p')
+                .long("preserve-permissions")
+                .help(PRESERVE_PERMISSIONS_HELP.as_str()),
+        ])
+        .args(rename())
+        .args(limits())
 }
 
 #[cfg(test)]
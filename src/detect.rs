@@ -0,0 +1,255 @@
+//! Detect archive formats from the content of a file
+//!
+//! Only the outer most format can be detected, an archive nested inside of a compressed
+//! stream becomes visible after decompressing it.
+
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use log::warn;
+
+use crate::{
+    error::{Error, Result},
+    ArchiveFormat,
+};
+
+/// Number of leading bytes needed to detect every supported format
+///
+/// The tar magic is located at the end of this range.
+pub const PEEK_SIZE: usize = 512;
+
+const TAR_MAGIC_OFFSET: usize = 257;
+const TAR_MAGIC: &[u8] = b"ustar";
+
+const GZIP_MAGIC: &[u8] = b"\x1F\x8B";
+const XZ_MAGIC: &[u8] = b"\xFD7zXZ\x00";
+const ZSTD_MAGIC: &[u8] = b"\x28\xB5\x2F\xFD";
+const BZIP2_MAGIC: &[u8] = b"BZh";
+const LZIP_MAGIC: &[u8] = b"LZIP";
+const ZIP_MAGICS: [&[u8]; 3] = [b"PK\x03\x04", b"PK\x05\x06", b"PK\x07\x08"];
+
+/// Properties byte of `.lzma` files written with the default lc=3, lp=0, pb=2
+const LZMA_PROPERTIES: u8 = 0x5D;
+
+/// Largest uncompressed size of a `.lzma` file considered plausible
+const LZMA_MAX_SIZE: u64 = 1 << 38;
+
+/// Identify the format of the data starting with `bytes`
+///
+/// `bytes` should hold at least [PEEK_SIZE] bytes unless the data is shorter.
+pub fn detect_bytes(bytes: &[u8]) -> Option<ArchiveFormat> {
+    if bytes.starts_with(XZ_MAGIC) {
+        Some(ArchiveFormat::Xz)
+    } else if bytes.starts_with(ZSTD_MAGIC) {
+        Some(ArchiveFormat::Zstd)
+    } else if bytes.starts_with(LZIP_MAGIC) {
+        Some(ArchiveFormat::Lzip)
+    } else if bytes.starts_with(GZIP_MAGIC) {
+        Some(ArchiveFormat::GZip)
+    } else if bytes.starts_with(BZIP2_MAGIC) && matches!(bytes.get(3), Some(b'1'..=b'9')) {
+        Some(ArchiveFormat::Bzip2)
+    } else if ZIP_MAGICS.iter().any(|magic| bytes.starts_with(magic)) {
+        Some(ArchiveFormat::Zip)
+    } else if bytes.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + TAR_MAGIC.len()) == Some(TAR_MAGIC) {
+        Some(ArchiveFormat::Tar)
+    } else if is_lzma_alone(bytes) {
+        Some(ArchiveFormat::LzmaAlone)
+    } else {
+        None
+    }
+}
+
+/// `.lzma` files have no magic, check the header for plausible values instead
+fn is_lzma_alone(bytes: &[u8]) -> bool {
+    let (dict_size, size) = match bytes {
+        [LZMA_PROPERTIES, d0, d1, d2, d3, s0, s1, s2, s3, s4, s5, s6, s7, ..] => (
+            u32::from_le_bytes([*d0, *d1, *d2, *d3]),
+            u64::from_le_bytes([*s0, *s1, *s2, *s3, *s4, *s5, *s6, *s7]),
+        ),
+        _ => return false,
+    };
+
+    // the xz tools only write dictionary sizes of 2^n or 2^n + 2^(n-1)
+    let dict_size_valid =
+        dict_size.is_power_of_two() || dict_size % 3 == 0 && (dict_size / 3).is_power_of_two();
+
+    dict_size_valid && (size == u64::MAX || size < LZMA_MAX_SIZE)
+}
+
+/// Identify the format of a seekable reader
+///
+/// The reader is rewound to the position it had before.
+pub fn detect<R>(reader: &mut R) -> io::Result<Option<ArchiveFormat>>
+where
+    R: Read + Seek,
+{
+    let position = reader.stream_position()?;
+
+    let mut buffer = Vec::with_capacity(PEEK_SIZE);
+    reader
+        .by_ref()
+        .take(PEEK_SIZE as u64)
+        .read_to_end(&mut buffer)?;
+
+    reader.seek(SeekFrom::Start(position))?;
+
+    Ok(detect_bytes(&buffer))
+}
+
+/// Reader which detects the format of a non-seekable stream
+///
+/// The leading bytes are buffered and replayed before the rest of the stream.
+pub struct PeekReader<R>
+where
+    R: Read,
+{
+    reader: R,
+    buffer: Vec<u8>,
+    position: usize,
+}
+
+impl<R> PeekReader<R>
+where
+    R: Read,
+{
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut buffer = Vec::with_capacity(PEEK_SIZE);
+        reader
+            .by_ref()
+            .take(PEEK_SIZE as u64)
+            .read_to_end(&mut buffer)?;
+
+        Ok(Self {
+            reader,
+            buffer,
+            position: 0,
+        })
+    }
+
+    /// Leading bytes of the stream
+    pub fn peek(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Format of the stream detected from the leading bytes
+    pub fn format(&self) -> Option<ArchiveFormat> {
+        detect_bytes(&self.buffer)
+    }
+}
+
+impl<R> Read for PeekReader<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position < self.buffer.len() {
+            let n = (&self.buffer[self.position..]).read(buf)?;
+            self.position += n;
+            return Ok(n);
+        }
+
+        self.reader.read(buf)
+    }
+}
+
+/// Determine the formats of the archive at `path`
+///
+/// The content is preferred over the file name. The formats derived from the file name are
+/// used if they agree with the content or the content is not recognized, otherwise a
+/// warning is logged and only the detected format is returned.
+pub fn detect_path(path: &Path) -> Result<Vec<ArchiveFormat>> {
+    let mut file = File::open(path).map_err(|source| Error::OpenArchiveFile {
+        source,
+        path: path.display().to_string(),
+    })?;
+
+    let detected = detect(&mut file).map_err(|source| Error::ReadArchiveFile {
+        source,
+        path: path.display().to_string(),
+    })?;
+    let from_name = ArchiveFormat::from_path(path);
+
+    match (detected, from_name.last()) {
+        (None, None) => Err(Error::UnknownArchiveFormat(path.display().to_string())),
+        (None, Some(_)) => Ok(from_name),
+        (Some(format), Some(outer)) if format == *outer => Ok(from_name),
+        (Some(format), Some(outer)) => {
+            warn!(
+                "{} is named like a {outer} archive but contains {format}",
+                path.display()
+            );
+            Ok(vec![format])
+        }
+        (Some(format), None) => Ok(vec![format]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{error::Error, io::Cursor};
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    /// test detection of all formats with a magic number
+    #[test]
+    fn detect_magic() {
+        let mut tar = vec![0; PEEK_SIZE];
+        tar[TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + 8].copy_from_slice(b"ustar  \0");
+
+        let cases: [(&[u8], Option<ArchiveFormat>); 10] = [
+            (&tar, Some(ArchiveFormat::Tar)),
+            (b"\x1F\x8B\x08\x00", Some(ArchiveFormat::GZip)),
+            (b"PK\x03\x04\x14\x00", Some(ArchiveFormat::Zip)),
+            (b"PK\x05\x06\x00\x00", Some(ArchiveFormat::Zip)),
+            (b"\xFD7zXZ\x00\x00\x04", Some(ArchiveFormat::Xz)),
+            (b"\x28\xB5\x2F\xFD\x04", Some(ArchiveFormat::Zstd)),
+            (b"BZh91AY&SY", Some(ArchiveFormat::Bzip2)),
+            (b"LZIP\x01\x0C", Some(ArchiveFormat::Lzip)),
+            (b"BZh", None),
+            (b"some text", None),
+        ];
+
+        for (bytes, format) in cases {
+            assert_eq!(detect_bytes(bytes), format, "{bytes:?}");
+        }
+    }
+
+    /// test detection of `.lzma` headers
+    #[test]
+    fn detect_lzma_alone() {
+        let mut header = vec![LZMA_PROPERTIES];
+        header.extend_from_slice(&(1u32 << 23).to_le_bytes());
+        header.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(detect_bytes(&header), Some(ArchiveFormat::LzmaAlone));
+
+        header[1..5].copy_from_slice(&(3u32 << 22).to_le_bytes());
+        assert_eq!(detect_bytes(&header), Some(ArchiveFormat::LzmaAlone));
+
+        header[1..5].copy_from_slice(&12345u32.to_le_bytes());
+        assert_eq!(detect_bytes(&header), None);
+    }
+
+    /// test detection rewinds the reader and the peek reader replays the stream
+    #[test]
+    fn detect_readers() -> Result<(), Box<dyn Error>> {
+        let data = b"\x1F\x8B some more data".to_vec();
+
+        let mut cursor = Cursor::new(data.clone());
+        assert_eq!(detect(&mut cursor)?, Some(ArchiveFormat::GZip));
+        assert_eq!(cursor.position(), 0);
+
+        let mut reader = PeekReader::new(&data[..])?;
+        assert_eq!(reader.format(), Some(ArchiveFormat::GZip));
+
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content)?;
+        assert_eq!(content, data);
+
+        Ok(())
+    }
+}
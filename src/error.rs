@@ -1,11 +1,27 @@
+use crate::{extract::Limit, ArchiveFormat};
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Invalid compression level '{0}'")]
     InvalidCompressionLevel(String),
 
+    #[error("{0} does not support compression levels")]
+    CompressionLevelNotSupported(ArchiveFormat),
+
+    #[error("Compression level {level} is not supported by {format}, expected {min} to {max}")]
+    CompressionLevelOutOfRange {
+        format: ArchiveFormat,
+        level: i32,
+        min: i32,
+        max: i32,
+    },
+
     #[error("Invalid compression format '{0}'")]
     InvalidCompressionFormat(String),
 
+    #[error("Invalid output format '{0}'")]
+    InvalidOutputFormat(String),
+
     #[error("Missing compression format argument")]
     MissingCompressionFormat,
 
@@ -15,6 +31,51 @@ pub enum Error {
     #[error("I18n Embed Error:")]
     I18nEmbed(#[from] i18n_embed::I18nEmbedError),
 
+    #[error("Invalid transform '{0}', expected s/regex/replacement/flags with the flags g and i")]
+    InvalidTransform(String),
+
+    #[error("Invalid regex in transform '{expression}'")]
+    TransformRegex {
+        expression: String,
+        #[source]
+        source: regex::Error,
+    },
+
+    #[error("Invalid SOURCE_DATE_EPOCH '{0}', expected seconds since the unix epoch")]
+    InvalidSourceDateEpoch(String),
+
+    #[error("Could not read patterns from '{path}'")]
+    ReadPatternFile {
+        #[backtrace]
+        source: std::io::Error,
+        path: String,
+    },
+
+    #[error("Output directory '{0}' does not exist, use --parents to create it")]
+    MissingOutputDirectory(String),
+
+    #[error("Could not create output directory '{path}'")]
+    CreateOutputDirectory {
+        #[backtrace]
+        source: std::io::Error,
+        path: String,
+    },
+
+    #[error("Archive exceeds {0}")]
+    LimitExceeded(Limit),
+
+    #[error("Invalid size '{0}', expected a number of bytes with an optional K, M, G or T suffix")]
+    InvalidSize(String),
+
+    #[error("Refusing to unpack '{0}' outside of the destination, use --unsafe-paths to allow it")]
+    UnsafeEntryPath(String),
+
+    #[error("Refusing to unpack link '{path}' to '{target}' outside of the destination, use --unsafe-paths to allow it")]
+    UnsafeLinkTarget { path: String, target: String },
+
+    #[error("Output file '{0}' already exists, use --overwrite to replace it")]
+    OutputExists(String),
+
     #[error("Could not create archive file '{path}'")]
     CreateArchiveFile {
         #[backtrace]
@@ -22,15 +83,186 @@ pub enum Error {
         path: String,
     },
 
-    #[error("Could not remove old archive file '{path}'")]
-    RemoveOldArchive {
+    #[error("Could not write archive file")]
+    WriteArchiveFile(#[source] std::io::Error),
+
+    #[error("{0} is a container and can only be used as the first format")]
+    ContainerNotInnermost(ArchiveFormat),
+
+    #[error("{format} can only compress a single file, add a container format like tar to pack {count} inputs")]
+    CodecMultipleInputs { format: ArchiveFormat, count: usize },
+
+    #[error("{format} can only compress a single file, add a container format like tar to pack directory '{path}'")]
+    CodecDirectory { format: ArchiveFormat, path: String },
+
+    #[error("{0} archives hold multiple entries and can not be unpacked to stdout")]
+    ContainerToStream(ArchiveFormat),
+
+    #[error("{0} can not pack stdin, only compression formats can read from stdin")]
+    ContainerFromStdin(ArchiveFormat),
+
+    #[error("Could not read the list of input files from '{path}'")]
+    ReadFileList {
+        #[backtrace]
+        source: std::io::Error,
+        path: String,
+    },
+
+    #[error("stdin can not hold both the list of input files and data to pack")]
+    StdinUsedTwice,
+
+    #[error("No input files to pack")]
+    NoInputFiles,
+
+    #[error("Could not read from stdin")]
+    ReadStdin(#[source] std::io::Error),
+
+    #[error("Writing to stdout requires an explicit format")]
+    StdoutRequiresFormat,
+
+    #[error("Could not remove input file '{path}'")]
+    RemoveInputFile {
+        #[backtrace]
+        source: std::io::Error,
+        path: String,
+    },
+
+    #[error("Input files can only be deleted for a single compressed file, not for {0} archives")]
+    DeleteInputNotSupported(ArchiveFormat),
+
+    #[error("Could not open archive file '{path}'")]
+    OpenArchiveFile {
         #[backtrace]
         source: std::io::Error,
         path: String,
     },
 
+    #[error("Could not read archive file '{path}'")]
+    ReadArchiveFile {
+        #[backtrace]
+        source: std::io::Error,
+        path: String,
+    },
+
+    #[error("Could not buffer nested {format} archive")]
+    BufferNestedArchive {
+        format: ArchiveFormat,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Could not open input file '{path}'")]
+    OpenInputFile {
+        #[backtrace]
+        source: std::io::Error,
+        path: String,
+    },
+
+    #[error("{format} can not contain directory '{path}'")]
+    DirectoryNotSupported { format: ArchiveFormat, path: String },
+
+    #[error("{format} can only contain a single file, can not add '{path}'")]
+    MultipleFilesNotSupported { format: ArchiveFormat, path: String },
+
+    #[error("Could not detect archive format of '{0}'")]
+    UnknownArchiveFormat(String),
+
+    #[error("Could not write output")]
+    WriteOutput(#[source] std::io::Error),
+
+    #[error("Could not serialize output")]
+    SerializeJson(#[from] serde_json::Error),
+
+    #[error("Could not serialize output")]
+    SerializeCsv(#[from] csv::Error),
+
     #[error("Tar Packer Error")]
     TarPacker(#[source] std::io::Error),
+
+    #[error("Tar Unpacker Error")]
+    TarUnpacker(#[source] std::io::Error),
+
+    #[error("Zip Packer Error")]
+    ZipPacker(#[source] zip::result::ZipError),
+
+    #[error("Zip Unpacker Error")]
+    ZipUnpacker(#[source] zip::result::ZipError),
+
+    #[error("GZip Packer Error")]
+    GZipPacker(#[source] std::io::Error),
+
+    #[error("GZip Unpacker Error")]
+    GZipUnpacker(#[source] std::io::Error),
+
+    #[error("Xz Packer Error")]
+    XzPacker(#[source] std::io::Error),
+
+    #[error("Xz Unpacker Error")]
+    XzUnpacker(#[source] std::io::Error),
+
+    #[error("Lzma Packer Error")]
+    LzmaPacker(#[source] std::io::Error),
+
+    #[error("Lzma Unpacker Error")]
+    LzmaUnpacker(#[source] std::io::Error),
+
+    #[error("Lzip Packer Error")]
+    LzipPacker(#[source] std::io::Error),
+
+    #[error("Lzip Unpacker Error")]
+    LzipUnpacker(#[source] std::io::Error),
+
+    #[error("Bzip2 Packer Error")]
+    Bzip2Packer(#[source] std::io::Error),
+
+    #[error("Bzip2 Unpacker Error")]
+    Bzip2Unpacker(#[source] std::io::Error),
+
+    #[error("Zstd Packer Error")]
+    ZstdPacker(#[source] std::io::Error),
+
+    #[error("Zstd Unpacker Error")]
+    ZstdUnpacker(#[source] std::io::Error),
 }
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    /// Exit code of the process when failing with this error
+    ///
+    /// Refusing to overwrite an existing file has its own code so scripts can tell it apart.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::OutputExists(_) => 3,
+            _ => 1,
+        }
+    }
+
+    /// Error of the packer of `format` for errors while writing its data
+    pub fn packer(format: ArchiveFormat, source: std::io::Error) -> Self {
+        match format {
+            ArchiveFormat::Tar => Self::TarPacker(source),
+            ArchiveFormat::Zip => Self::ZipPacker(source.into()),
+            ArchiveFormat::GZip => Self::GZipPacker(source),
+            ArchiveFormat::Xz => Self::XzPacker(source),
+            ArchiveFormat::LzmaAlone => Self::LzmaPacker(source),
+            ArchiveFormat::Lzip => Self::LzipPacker(source),
+            ArchiveFormat::Zstd => Self::ZstdPacker(source),
+            ArchiveFormat::Bzip2 => Self::Bzip2Packer(source),
+        }
+    }
+
+    /// Error of the unpacker of `format` for errors while reading its data
+    pub(crate) fn unpacker(format: ArchiveFormat, source: std::io::Error) -> Self {
+        match format {
+            ArchiveFormat::Tar => Self::TarUnpacker(source),
+            ArchiveFormat::Zip => Self::ZipUnpacker(source.into()),
+            ArchiveFormat::GZip => Self::GZipUnpacker(source),
+            ArchiveFormat::Xz => Self::XzUnpacker(source),
+            ArchiveFormat::LzmaAlone => Self::LzmaUnpacker(source),
+            ArchiveFormat::Lzip => Self::LzipUnpacker(source),
+            ArchiveFormat::Zstd => Self::ZstdUnpacker(source),
+            ArchiveFormat::Bzip2 => Self::Bzip2Unpacker(source),
+        }
+    }
+}
@@ -0,0 +1,660 @@
+//! Options shared by all unpackers
+//!
+//! Every [Unpacker](crate::packer::Unpacker) writes its entries through a [Destination] which
+//! keeps them inside of the destination directory and resolves collisions with existing files,
+//! so all formats behave the same. The [Limits] protect against decompression bombs.
+
+use std::{
+    fmt,
+    fs::{self, Metadata},
+    io::{self, ErrorKind, Read, Write},
+    path::{Component, Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use log::debug;
+
+use crate::{
+    error::{Error, Result},
+    rename::Rename,
+    ArchiveFormat,
+};
+
+/// How to handle an entry whose destination exists already
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conflict {
+    /// Replace the existing file
+    Overwrite,
+    /// Keep the existing file and skip the entry
+    Skip,
+    /// Only replace the existing file if the entry is newer
+    KeepNewer,
+    /// Write the entry next to the existing file with `.1`, `.2`, ... appended
+    Rename,
+    /// Move the existing file to a backup with `~` appended before replacing it
+    Backup,
+}
+
+/// Existing files are replaced like tar does
+impl Default for Conflict {
+    fn default() -> Self {
+        Self::Overwrite
+    }
+}
+
+/// Options applied while unpacking an archive
+#[derive(Debug, Clone, Default)]
+pub struct UnpackOptions {
+    pub conflict: Conflict,
+    /// Allow entries with absolute paths, `..` components and links leading outside of the
+    /// destination
+    pub unsafe_paths: bool,
+    /// Restore setuid, setgid and sticky bits, only the permission bits are restored otherwise
+    pub preserve_permissions: bool,
+    pub limits: Limits,
+    /// Rewriting of the entry names
+    pub rename: Rename,
+}
+
+/// Compression ratios are only checked once this many bytes were decompressed
+///
+/// Small files of repeated data reach high ratios without being a threat.
+const RATIO_THRESHOLD: u64 = 1 << 20;
+
+/// Limits protecting against archives which decompress to far more than they appear to hold
+///
+/// `None` disables a limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Uncompressed bytes of all entries together
+    pub max_total_size: Option<u64>,
+    pub max_entries: Option<u64>,
+    /// Uncompressed bytes of a single entry
+    pub max_entry_size: Option<u64>,
+    /// Uncompressed bytes per compressed byte
+    pub max_ratio: Option<u64>,
+    /// Layers peeled off a nested archive including the innermost one
+    pub max_layers: Option<usize>,
+}
+
+impl Limits {
+    /// Limits which never abort
+    pub const fn unlimited() -> Self {
+        Self {
+            max_total_size: None,
+            max_entries: None,
+            max_entry_size: None,
+            max_ratio: None,
+            max_layers: None,
+        }
+    }
+}
+
+/// Generous limits which only stop archives no real data produces
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_total_size: Some(64 << 30),
+            max_entries: Some(1_000_000),
+            max_entry_size: Some(16 << 30),
+            max_ratio: Some(1000),
+            max_layers: Some(8),
+        }
+    }
+}
+
+/// A limit which was exceeded, holding its maximum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    TotalSize(u64),
+    Entries(u64),
+    EntrySize(u64),
+    Ratio(u64),
+    Layers(usize),
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::TotalSize(max) => write!(
+                f,
+                "the maximum total size of {max} bytes, use --max-size to raise it"
+            ),
+            Limit::Entries(max) => write!(
+                f,
+                "the maximum of {max} entries, use --max-entries to raise it"
+            ),
+            Limit::EntrySize(max) => write!(
+                f,
+                "the maximum entry size of {max} bytes, use --max-entry-size to raise it"
+            ),
+            Limit::Ratio(max) => write!(
+                f,
+                "the maximum compression ratio of {max}, use --max-ratio to raise it"
+            ),
+            Limit::Layers(max) => write!(
+                f,
+                "the maximum of {max} nested layers, use --max-layers to raise it"
+            ),
+        }
+    }
+}
+
+/// Counts the entries of an archive against the [Limits]
+pub(crate) struct Budget {
+    limits: Limits,
+    entries: u64,
+    total_size: u64,
+}
+
+impl Budget {
+    pub(crate) fn new(limits: &Limits) -> Self {
+        Self {
+            limits: *limits,
+            entries: 0,
+            total_size: 0,
+        }
+    }
+
+    /// Account for an entry of `size` uncompressed bytes
+    pub(crate) fn add_entry(&mut self, size: u64) -> Result<()> {
+        self.entries += 1;
+        self.total_size = self.total_size.saturating_add(size);
+
+        check_limit(self.limits.max_entries, self.entries, Limit::Entries)?;
+        check_limit(self.limits.max_entry_size, size, Limit::EntrySize)?;
+        check_limit(
+            self.limits.max_total_size,
+            self.total_size,
+            Limit::TotalSize,
+        )
+    }
+}
+
+/// Check the ratio of `uncompressed` to `compressed` bytes
+pub(crate) fn check_ratio(limits: &Limits, uncompressed: u64, compressed: u64) -> Result<()> {
+    if uncompressed <= RATIO_THRESHOLD {
+        return Ok(());
+    }
+
+    check_limit(
+        limits.max_ratio,
+        uncompressed / compressed.max(1),
+        Limit::Ratio,
+    )
+}
+
+/// Check `value` against the maximum of `limit`, a missing maximum allows any value
+pub(crate) fn check_limit(max: Option<u64>, value: u64, limit: fn(u64) -> Limit) -> Result<()> {
+    match max {
+        Some(max) if value > max => Err(Error::LimitExceeded(limit(max))),
+        _ => Ok(()),
+    }
+}
+
+/// Copy a single file stream, which is the only entry of its archive, within the limits
+pub(crate) fn copy_stream<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    format: ArchiveFormat,
+    limits: &Limits,
+) -> Result<u64>
+where
+    R: Read,
+    W: Write,
+{
+    let (max, limit) = [
+        limits
+            .max_entry_size
+            .map(|max| (max, Limit::EntrySize(max))),
+        limits
+            .max_total_size
+            .map(|max| (max, Limit::TotalSize(max))),
+    ]
+    .into_iter()
+    .flatten()
+    .min_by_key(|(max, _)| *max)
+    .unwrap_or((u64::MAX, Limit::TotalSize(u64::MAX)));
+
+    // one byte more than allowed tells a stream at the limit from a larger one
+    let size = io::copy(&mut reader.take(max.saturating_add(1)), writer)
+        .map_err(|source| Error::unpacker(format, source))?;
+
+    if size > max {
+        Err(Error::LimitExceeded(limit))
+    } else {
+        Ok(size)
+    }
+}
+
+/// Parse a size in bytes with an optional binary `K`, `M`, `G` or `T` suffix
+pub fn parse_size(value: &str) -> Result<u64> {
+    let invalid = || Error::InvalidSize(value.to_owned());
+
+    let trimmed = value.trim();
+    let (number, shift) = match trimmed.char_indices().last() {
+        Some((index, suffix)) if suffix.is_ascii_alphabetic() => {
+            let shift = match suffix.to_ascii_uppercase() {
+                'K' => 10,
+                'M' => 20,
+                'G' => 30,
+                'T' => 40,
+                _ => return Err(invalid()),
+            };
+
+            (&trimmed[..index], shift)
+        }
+        _ => (trimmed, 0),
+    };
+
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(1 << shift))
+        .ok_or_else(invalid)
+}
+
+/// Directory an archive is unpacked into
+pub(crate) struct Destination<'a> {
+    root: PathBuf,
+    /// Resolved symlinks are compared against the canonical root
+    canonical_root: PathBuf,
+    format: ArchiveFormat,
+    options: &'a UnpackOptions,
+}
+
+impl<'a> Destination<'a> {
+    /// Create the directory `root` to unpack an archive of `format` into
+    pub(crate) fn create(
+        root: &Path,
+        format: ArchiveFormat,
+        options: &'a UnpackOptions,
+    ) -> Result<Self> {
+        let map_err = |source| Error::unpacker(format, source);
+
+        fs::create_dir_all(root).map_err(map_err)?;
+        let canonical_root = root.canonicalize().map_err(map_err)?;
+
+        Ok(Self {
+            root: root.to_owned(),
+            canonical_root,
+            format,
+            options,
+        })
+    }
+
+    /// Path the entry `name` is unpacked to
+    ///
+    /// Names with a root or `..` components are refused as well as names below a symlink
+    /// leading outside of the destination, which an earlier entry could have created. Returns
+    /// `None` for an empty name or a name with all components stripped.
+    pub(crate) fn entry_path(&self, name: &Path) -> Result<Option<PathBuf>> {
+        let name = match self.options.rename.apply(name) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        let name = name.as_path();
+
+        let refuse = || Error::UnsafeEntryPath(name.display().to_string());
+
+        let mut path = self.root.clone();
+
+        for component in name.components() {
+            match component {
+                Component::CurDir => continue,
+                Component::Normal(part) => path.push(part),
+                Component::Prefix(_) | Component::RootDir | Component::ParentDir
+                    if self.options.unsafe_paths =>
+                {
+                    return Ok(Some(self.root.join(name)));
+                }
+                Component::Prefix(_) | Component::RootDir | Component::ParentDir => {
+                    return Err(refuse())
+                }
+            }
+        }
+
+        if path == self.root {
+            return Ok(None);
+        }
+
+        if self.options.unsafe_paths {
+            return Ok(Some(path));
+        }
+
+        let mut parent = self.root.clone();
+
+        for component in name.parent().into_iter().flat_map(Path::components) {
+            parent.push(component);
+
+            match fs::symlink_metadata(&parent) {
+                Ok(metadata) if metadata.file_type().is_symlink() => {
+                    // a dangling symlink can not be written through either
+                    let resolved = parent.canonicalize().map_err(|_| refuse())?;
+
+                    if !resolved.starts_with(&self.canonical_root) {
+                        return Err(refuse());
+                    }
+                }
+                Ok(_) => {}
+                Err(error) if error.kind() == ErrorKind::NotFound => break,
+                Err(error) => return Err(Error::unpacker(self.format, error)),
+            }
+        }
+
+        Ok(Some(path))
+    }
+
+    /// Check that a symlink at `path` pointing to `target` stays inside of the destination
+    ///
+    /// The target is resolved through the symlinks already in the destination, so a chain of
+    /// links created by earlier entries can not lead outside either.
+    pub(crate) fn check_symlink(&self, path: &Path, target: &Path) -> Result<()> {
+        if self.options.unsafe_paths {
+            return Ok(());
+        }
+
+        let refuse = || Error::UnsafeLinkTarget {
+            path: path.display().to_string(),
+            target: target.display().to_string(),
+        };
+
+        let parent = path
+            .parent()
+            .and_then(|parent| parent.strip_prefix(&self.root).ok())
+            .ok_or_else(refuse)?;
+
+        match self.resolve(&parent.join(target)) {
+            Some(resolved) if resolved.starts_with(&self.canonical_root) => Ok(()),
+            _ => Err(refuse()),
+        }
+    }
+
+    /// Resolve a path relative to the destination through the symlinks existing in it
+    ///
+    /// Returns `None` for absolute paths and dangling symlinks.
+    fn resolve(&self, relative_path: &Path) -> Option<PathBuf> {
+        let mut resolved = self.canonical_root.clone();
+
+        for component in relative_path.components() {
+            match component {
+                Component::CurDir => {}
+                // the resolved path never contains symlinks, so `..` is its parent
+                Component::ParentDir => {
+                    resolved.pop();
+                }
+                Component::Normal(part) => {
+                    resolved.push(part);
+
+                    if matches!(fs::symlink_metadata(&resolved), Ok(metadata) if metadata.file_type().is_symlink())
+                    {
+                        resolved = resolved.canonicalize().ok()?;
+                    }
+                }
+                Component::Prefix(_) | Component::RootDir => return None,
+            }
+        }
+
+        Some(resolved)
+    }
+
+    /// Existing file a hard link at `path` to the entry `target` is created from
+    pub(crate) fn link_source(&self, path: &Path, target: &Path) -> Result<PathBuf> {
+        let refuse = || Error::UnsafeLinkTarget {
+            path: path.display().to_string(),
+            target: target.display().to_string(),
+        };
+
+        match self.entry_path(target) {
+            Ok(Some(source)) => Ok(source),
+            Ok(None) | Err(Error::UnsafeEntryPath(_)) => Err(refuse()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Prepare `path` for an entry according to the conflict policy and create its parent
+    ///
+    /// See [prepare_destination].
+    pub(crate) fn prepare(
+        &self,
+        path: &Path,
+        is_dir: bool,
+        mtime: Option<u64>,
+    ) -> Result<Option<PathBuf>> {
+        let map_err = |source| Error::unpacker(self.format, source);
+
+        let path = match prepare_destination(path, is_dir, mtime, self.options.conflict)
+            .map_err(map_err)?
+        {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(map_err)?;
+        }
+
+        Ok(Some(path))
+    }
+}
+
+/// Prepare the destination of an entry according to the conflict policy
+///
+/// Returns the path the entry is written to or `None` if it is skipped. A directory entry is
+/// merged into an existing directory without applying the policy.
+pub(crate) fn prepare_destination(
+    path: &Path,
+    is_dir: bool,
+    mtime: Option<u64>,
+    conflict: Conflict,
+) -> io::Result<Option<PathBuf>> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Some(path.to_owned())),
+        Err(error) => return Err(error),
+    };
+
+    if is_dir && metadata.is_dir() {
+        return Ok(Some(path.to_owned()));
+    }
+
+    match conflict {
+        Conflict::Overwrite => {
+            remove(path, &metadata)?;
+
+            Ok(Some(path.to_owned()))
+        }
+        Conflict::Skip => {
+            debug!("Skipping existing file {}", path.display());
+
+            Ok(None)
+        }
+        Conflict::KeepNewer => {
+            let existing_mtime = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs());
+
+            // without a time on both sides the entry can not be proven newer
+            match (mtime, existing_mtime) {
+                (Some(mtime), Some(existing_mtime)) if mtime > existing_mtime => {
+                    remove(path, &metadata)?;
+
+                    Ok(Some(path.to_owned()))
+                }
+                _ => {
+                    debug!("Keeping newer existing file {}", path.display());
+
+                    Ok(None)
+                }
+            }
+        }
+        Conflict::Rename => {
+            let renamed = (1..)
+                .map(|index| with_suffix(path, &format!(".{index}")))
+                .find(|renamed| fs::symlink_metadata(renamed).is_err())
+                .expect("there is always an unused name");
+
+            debug!(
+                "Writing {} to {} to keep the existing file",
+                path.display(),
+                renamed.display()
+            );
+
+            Ok(Some(renamed))
+        }
+        Conflict::Backup => {
+            let backup = with_suffix(path, "~");
+
+            debug!(
+                "Moving existing file {} to {}",
+                path.display(),
+                backup.display()
+            );
+
+            fs::rename(path, &backup)?;
+
+            Ok(Some(path.to_owned()))
+        }
+    }
+}
+
+/// Remove an existing file so a symlink at its place is replaced and not followed
+///
+/// Directories are only removed if they are empty.
+fn remove(path: &Path, metadata: &Metadata) -> io::Result<()> {
+    if metadata.is_dir() {
+        fs::remove_dir(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Append `suffix` to the file name of `path`
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(ToOwned::to_owned).unwrap_or_default();
+    name.push(suffix);
+
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use filetime::FileTime;
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    /// test every policy against an existing file
+    #[test]
+    fn resolve_conflicts() -> Result<(), Box<dyn std::error::Error>> {
+        // setup working directory
+
+        let working_directory = tempdir()?;
+        let path = working_directory.path().join("file.txt");
+        fs::write(&path, "existing")?;
+        filetime::set_file_mtime(&path, FileTime::from_unix_time(1000, 0))?;
+
+        // run tests
+
+        assert_eq!(
+            prepare_destination(&path, false, Some(2000), Conflict::Skip)?,
+            None
+        );
+        assert_eq!(
+            prepare_destination(&path, false, Some(500), Conflict::KeepNewer)?,
+            None
+        );
+        assert_eq!(
+            prepare_destination(&path, false, None, Conflict::KeepNewer)?,
+            None
+        );
+
+        fs::write(working_directory.path().join("file.txt.1"), "renamed")?;
+        assert_eq!(
+            prepare_destination(&path, false, None, Conflict::Rename)?,
+            Some(working_directory.path().join("file.txt.2"))
+        );
+
+        assert_eq!(
+            prepare_destination(&path, false, None, Conflict::Backup)?,
+            Some(path.clone())
+        );
+        assert!(!path.exists());
+        assert_eq!(
+            fs::read_to_string(working_directory.path().join("file.txt~"))?,
+            "existing"
+        );
+
+        fs::write(&path, "existing")?;
+        filetime::set_file_mtime(&path, FileTime::from_unix_time(1000, 0))?;
+        assert_eq!(
+            prepare_destination(&path, false, Some(2000), Conflict::KeepNewer)?,
+            Some(path.clone())
+        );
+        assert!(!path.exists());
+
+        fs::write(&path, "existing")?;
+        assert_eq!(
+            prepare_destination(&path, false, None, Conflict::Overwrite)?,
+            Some(path.clone())
+        );
+        assert!(!path.exists());
+
+        // existing directories are merged
+
+        assert_eq!(
+            prepare_destination(working_directory.path(), true, None, Conflict::Skip)?,
+            Some(working_directory.path().to_owned())
+        );
+
+        Ok(())
+    }
+
+    /// test entries are counted against the limits
+    #[test]
+    fn budget() {
+        let limits = Limits {
+            max_total_size: Some(10),
+            max_entries: Some(3),
+            max_entry_size: Some(6),
+            ..Limits::unlimited()
+        };
+
+        let mut budget = Budget::new(&limits);
+        assert!(budget.add_entry(6).is_ok());
+        assert!(matches!(
+            budget.add_entry(7),
+            Err(Error::LimitExceeded(Limit::EntrySize(6)))
+        ));
+
+        let mut budget = Budget::new(&limits);
+        assert!(budget.add_entry(6).is_ok());
+        assert!(matches!(
+            budget.add_entry(5),
+            Err(Error::LimitExceeded(Limit::TotalSize(10)))
+        ));
+
+        let mut budget = Budget::new(&limits);
+        for _ in 0..3 {
+            assert!(budget.add_entry(0).is_ok());
+        }
+        assert!(matches!(
+            budget.add_entry(0),
+            Err(Error::LimitExceeded(Limit::Entries(3)))
+        ));
+    }
+
+    /// test sizes with suffixes
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("512").ok(), Some(512));
+        assert_eq!(parse_size("4k").ok(), Some(4096));
+        assert_eq!(parse_size("2G").ok(), Some(2 << 30));
+        assert!(parse_size("2X").is_err());
+        assert!(parse_size("G").is_err());
+        assert!(parse_size("99999999T").is_err());
+    }
+}
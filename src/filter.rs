@@ -0,0 +1,414 @@
+//! Exclude and include patterns applied while walking directories
+//!
+//! Patterns use the syntax of `.gitignore` files: `*` and `?` match within a path component,
+//! `**` matches any number of components, `[a-z]` matches a class of characters, a leading
+//! `!` includes paths again, a trailing `/` only matches directories and a pattern containing
+//! a `/` is matched against the whole path instead of just the file name.
+
+use std::{
+    ffi::OsString,
+    fs,
+    io::ErrorKind,
+    path::{Component, Path},
+};
+
+use crate::error::{Error, Result};
+
+/// File of patterns read from every walked directory by default
+pub const BRICKIGNORE: &str = ".brickignore";
+
+/// Patterns file of git
+pub const GITIGNORE: &str = ".gitignore";
+
+/// Directories and files of version control systems excluded by [Filter::exclude_vcs]
+const VCS_PATTERNS: &[&str] = &[
+    ".git",
+    ".gitattributes",
+    ".gitignore",
+    ".gitmodules",
+    ".hg",
+    ".hgignore",
+    ".hgtags",
+    ".svn",
+    ".bzr",
+    ".bzrignore",
+    "CVS",
+    ".cvsignore",
+    "_darcs",
+];
+
+/// Which paths to leave out while walking directories
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    /// Patterns matched against the path below the walked directory
+    ///
+    /// Later patterns override earlier ones and all of them override ignore files.
+    pub patterns: Vec<Pattern>,
+    /// Names of files whose patterns apply to the directory containing them
+    pub ignore_files: Vec<OsString>,
+}
+
+/// Only `.brickignore` files are honored
+impl Default for Filter {
+    fn default() -> Self {
+        Self {
+            patterns: Vec::new(),
+            ignore_files: vec![BRICKIGNORE.into()],
+        }
+    }
+}
+
+impl Filter {
+    /// Leave out paths matching the glob
+    pub fn exclude(&mut self, glob: &str) {
+        self.patterns.push(Pattern::new(glob, false));
+    }
+
+    /// Add paths matching the glob even if an earlier pattern excluded them
+    pub fn include(&mut self, glob: &str) {
+        self.patterns.push(Pattern::new(glob, true));
+    }
+
+    /// Leave out the metadata of version control systems like `.git`
+    pub fn exclude_vcs(&mut self) {
+        for glob in VCS_PATTERNS {
+            self.exclude(glob);
+        }
+    }
+
+    /// Add the patterns of a file using the `.gitignore` syntax
+    pub fn exclude_from(&mut self, path: &Path) -> Result<()> {
+        let content = fs::read_to_string(path).map_err(|source| Error::ReadPatternFile {
+            source,
+            path: path.display().to_string(),
+        })?;
+
+        self.patterns.extend(parse_patterns(&content));
+
+        Ok(())
+    }
+
+    /// Whether a path is left out, `path` is relative to the walked directory
+    pub(crate) fn is_excluded(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        is_excluded(&self.patterns, path, is_dir)
+    }
+
+    /// Read the patterns of all ignore files in `directory`
+    ///
+    /// Missing files are skipped.
+    pub(crate) fn read_ignore_files(&self, directory: &Path) -> Result<Vec<Pattern>> {
+        let mut patterns = Vec::new();
+
+        for name in &self.ignore_files {
+            let path = directory.join(name);
+
+            match fs::read_to_string(&path) {
+                Ok(content) => patterns.extend(parse_patterns(&content)),
+                Err(error) if error.kind() == ErrorKind::NotFound => {}
+                Err(source) => {
+                    return Err(Error::ReadPatternFile {
+                        source,
+                        path: path.display().to_string(),
+                    })
+                }
+            }
+        }
+
+        Ok(patterns)
+    }
+}
+
+/// Whether the last pattern matching `path` excludes it
+///
+/// Returns `None` if no pattern matches.
+pub(crate) fn is_excluded(patterns: &[Pattern], path: &Path, is_dir: bool) -> Option<bool> {
+    let path = slash_path(path);
+
+    patterns
+        .iter()
+        .rev()
+        .find(|pattern| pattern.matches(&path, is_dir))
+        .map(|pattern| !pattern.include)
+}
+
+/// Parse the lines of a `.gitignore` style file skipping blank lines and comments
+pub(crate) fn parse_patterns(content: &str) -> Vec<Pattern> {
+    content
+        .lines()
+        .map(|line| line.trim_end())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| match line.strip_prefix('!') {
+            Some(glob) => Pattern::new(glob, true),
+            // a backslash escapes a leading `!` or `#`
+            None => Pattern::new(line.strip_prefix('\\').unwrap_or(line), false),
+        })
+        .collect()
+}
+
+/// Components of a relative path joined with `/`
+fn slash_path(path: &Path) -> Vec<char> {
+    let mut slash_path = Vec::new();
+
+    for component in path.components() {
+        if let Component::Normal(name) = component {
+            if !slash_path.is_empty() {
+                slash_path.push('/');
+            }
+            slash_path.extend(name.to_string_lossy().chars());
+        }
+    }
+
+    slash_path
+}
+
+/// A single exclude or include pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    tokens: Vec<Token>,
+    /// Include matching paths instead of excluding them
+    include: bool,
+    /// Only match directories
+    directory: bool,
+}
+
+impl Pattern {
+    /// Parse a glob which excludes or with `include` includes matching paths
+    pub fn new(glob: &str, include: bool) -> Self {
+        let (glob, directory) = match glob.strip_suffix('/') {
+            Some(glob) => (glob, true),
+            None => (glob, false),
+        };
+
+        let mut tokens = Vec::new();
+
+        // globs without a slash match the file name in any directory
+        match glob.strip_prefix('/') {
+            Some(glob) => tokenize(glob, &mut tokens),
+            None if glob.contains('/') => tokenize(glob, &mut tokens),
+            None => {
+                tokens.push(Token::Directories);
+                tokenize(glob, &mut tokens);
+            }
+        }
+
+        Self {
+            tokens,
+            include,
+            directory,
+        }
+    }
+
+    fn matches(&self, path: &[char], is_dir: bool) -> bool {
+        (is_dir || !self.directory) && match_tokens(&self.tokens, path)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    /// `?` matching a single character except `/`
+    Any,
+    /// `*` matching any characters except `/`
+    Star,
+    /// `**/` matching any number of leading directories
+    Directories,
+    /// `/**` matching everything below a directory
+    Everything,
+    /// `[...]` matching a single character in or with `negated` not in the ranges
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+fn tokenize(glob: &str, tokens: &mut Vec<Token>) {
+    let chars = glob.chars().collect::<Vec<_>>();
+    let mut index = 0;
+
+    while index < chars.len() {
+        let at_component_start = index == 0 || chars[index - 1] == '/';
+
+        match chars[index] {
+            '*' if chars.get(index + 1) == Some(&'*') && at_component_start => {
+                match chars.get(index + 2) {
+                    Some('/') => {
+                        tokens.push(Token::Directories);
+                        index += 3;
+                        continue;
+                    }
+                    None => {
+                        // `a/**` matches everything in `a` but not `a` itself
+                        tokens.push(Token::Everything);
+                        index += 2;
+                        continue;
+                    }
+                    Some(_) => tokens.push(Token::Star),
+                }
+            }
+            '*' => tokens.push(Token::Star),
+            '?' => tokens.push(Token::Any),
+            '[' => match parse_class(&chars[index + 1..]) {
+                Some((token, length)) => {
+                    tokens.push(token);
+                    index += length + 1;
+                    continue;
+                }
+                // an unclosed bracket is matched literally
+                None => tokens.push(Token::Char('[')),
+            },
+            '\\' if index + 1 < chars.len() => {
+                index += 1;
+                tokens.push(Token::Char(chars[index]));
+            }
+            c => tokens.push(Token::Char(c)),
+        }
+
+        index += 1;
+    }
+}
+
+/// Parse a character class following its `[`, returns the class and the characters consumed
+fn parse_class(chars: &[char]) -> Option<(Token, usize)> {
+    let mut index = 0;
+    let negated = matches!(chars.first(), Some('!') | Some('^'));
+    if negated {
+        index += 1;
+    }
+
+    let mut ranges = Vec::new();
+
+    // a `]` right at the start is part of the class
+    while index < chars.len() && (chars[index] != ']' || ranges.is_empty()) {
+        let start = chars[index];
+
+        if chars.get(index + 1) == Some(&'-')
+            && matches!(chars.get(index + 2), Some(c) if *c != ']')
+        {
+            ranges.push((start, chars[index + 2]));
+            index += 3;
+        } else {
+            ranges.push((start, start));
+            index += 1;
+        }
+    }
+
+    (index < chars.len()).then(|| (Token::Class { negated, ranges }, index + 1))
+}
+
+/// Whether `path` matches all of `tokens`
+///
+/// Results are memoized per token and path position, so patterns with many `*` and `**` do not
+/// backtrack exponentially.
+fn match_tokens(tokens: &[Token], path: &[char]) -> bool {
+    let mut memo = vec![None; (tokens.len() + 1) * (path.len() + 1)];
+
+    match_from(tokens, path, 0, 0, &mut memo)
+}
+
+/// Whether the path from `start` on matches the tokens from `token` on
+fn match_from(
+    tokens: &[Token],
+    path: &[char],
+    token: usize,
+    start: usize,
+    memo: &mut [Option<bool>],
+) -> bool {
+    let key = token * (path.len() + 1) + start;
+
+    if let Some(matched) = memo[key] {
+        return matched;
+    }
+
+    let rest = &path[start..];
+    let mut next = |start| match_from(tokens, path, token + 1, start, memo);
+
+    let matched = match tokens.get(token) {
+        None => rest.is_empty(),
+        Some(Token::Char(c)) => rest.first() == Some(c) && next(start + 1),
+        Some(Token::Any) => matches!(rest.first(), Some(c) if *c != '/') && next(start + 1),
+        Some(Token::Class { negated, ranges }) => {
+            matches!(rest.first(), Some(c) if *c != '/'
+                && ranges.iter().any(|(first, last)| (*first..=*last).contains(c)) != *negated)
+                && next(start + 1)
+        }
+        Some(Token::Star) => (0..=rest.len())
+            .take_while(|&length| length == 0 || rest[length - 1] != '/')
+            .any(|length| next(start + length)),
+        Some(Token::Directories) => {
+            next(start)
+                || rest
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| **c == '/')
+                    .any(|(index, _)| next(start + index + 1))
+        }
+        Some(Token::Everything) => (1..=rest.len()).any(|length| next(start + length)),
+    };
+
+    memo[key] = Some(matched);
+
+    matched
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn excluded(patterns: &str, path: &str, is_dir: bool) -> Option<bool> {
+        is_excluded(&parse_patterns(patterns), Path::new(path), is_dir)
+    }
+
+    /// test glob syntax of patterns
+    #[test]
+    fn match_patterns() {
+        let cases = [
+            ("target", "target", true, Some(true)),
+            ("target", "crate/target", true, Some(true)),
+            ("target/", "target", false, None),
+            ("/target", "crate/target", true, None),
+            ("*.log", "logs/debug.log", false, Some(true)),
+            ("*.log", "debug.log.gz", false, None),
+            ("src/*.rs", "src/main.rs", false, Some(true)),
+            ("src/*.rs", "src/bin/main.rs", false, None),
+            ("src/**/*.rs", "src/main.rs", false, Some(true)),
+            ("src/**/*.rs", "src/bin/deep/main.rs", false, Some(true)),
+            ("**/node_modules", "web/node_modules", true, Some(true)),
+            ("build/**", "build", true, None),
+            ("build/**", "build/out/file", false, Some(true)),
+            ("file?.txt", "file1.txt", false, Some(true)),
+            ("file?.txt", "file10.txt", false, None),
+            ("file[0-9].txt", "file7.txt", false, Some(true)),
+            ("file[!0-9].txt", "file7.txt", false, None),
+            ("[unclosed", "[unclosed", false, Some(true)),
+            ("\\#hash", "#hash", false, Some(true)),
+            ("# comment", "# comment", false, None),
+            ("*.log\n!keep.log", "keep.log", false, Some(false)),
+            ("*.log\n!keep.log", "drop.log", false, Some(true)),
+        ];
+
+        for (patterns, path, is_dir, expected) in cases {
+            assert_eq!(
+                excluded(patterns, path, is_dir),
+                expected,
+                "{patterns:?} on {path}"
+            );
+        }
+    }
+
+    /// test patterns which backtrack exponentially without memoization
+    #[test]
+    fn pathological_patterns() {
+        let name = "a".repeat(64);
+        let path = vec!["a"; 32].join("/");
+
+        assert_eq!(excluded(&"*a".repeat(16), &format!("{name}b"), false), None);
+        assert_eq!(
+            excluded(&format!("{}b", "a/**/".repeat(12)), &path, false),
+            None
+        );
+        assert_eq!(
+            excluded(&format!("{}/b", "**/a".repeat(12)), &path, false),
+            None
+        );
+    }
+}
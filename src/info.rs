@@ -0,0 +1,255 @@
+use std::{
+    cell::{Cell, RefCell},
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use serde::{Serialize, Serializer};
+
+use crate::{
+    error::{Error, Result},
+    extract::Limits,
+    nested::NestedUnpacker,
+    packer::Unpacker,
+    ArchiveFormat,
+};
+
+/// Type of an entry in an archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryType {
+    File,
+    Directory,
+    Symlink,
+    HardLink,
+    Other,
+}
+
+impl EntryType {
+    /// Character used for the entry type in a `ls -l` style listing
+    pub fn as_char(&self) -> char {
+        match self {
+            EntryType::File => '-',
+            EntryType::Directory => 'd',
+            EntryType::Symlink => 'l',
+            EntryType::HardLink => 'h',
+            EntryType::Other => '?',
+        }
+    }
+}
+
+/// Metadata of a single entry in an archive
+#[derive(Debug, Clone, Serialize)]
+pub struct Entry {
+    /// Path inside the archive, serialized lossily if it is not valid UTF-8
+    #[serde(serialize_with = "serialize_path")]
+    pub path: PathBuf,
+    #[serde(rename = "type")]
+    pub entry_type: EntryType,
+    /// Uncompressed size in bytes
+    pub size: u64,
+    /// Unix permission bits
+    pub mode: u32,
+    pub uid: u64,
+    pub gid: u64,
+    pub user: Option<String>,
+    pub group: Option<String>,
+    /// Modification time in seconds since the unix epoch
+    pub mtime: u64,
+    /// Target of a symlink or hard link
+    #[serde(serialize_with = "serialize_link_target")]
+    pub link_target: Option<PathBuf>,
+}
+
+/// Serialize a path as a string, replacing invalid UTF-8 with U+FFFD
+fn serialize_path<S>(path: &Path, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&path.to_string_lossy())
+}
+
+fn serialize_link_target<S>(target: &Option<PathBuf>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match target {
+        Some(target) => serializer.serialize_some(&target.to_string_lossy()),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Totals over all entries of an archive
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Summary {
+    pub entries: u64,
+    /// Sum of the sizes of all entries in bytes
+    pub uncompressed_size: u64,
+    /// Size of the archive file in bytes
+    pub archive_size: u64,
+    /// Ratio of uncompressed size to archive size
+    pub compression_ratio: f64,
+}
+
+impl Summary {
+    pub fn new(entries: &[Entry], archive_size: u64) -> Self {
+        let uncompressed_size = entries.iter().map(|entry| entry.size).sum();
+
+        let compression_ratio = if archive_size == 0 {
+            0.0
+        } else {
+            uncompressed_size as f64 / archive_size as f64
+        };
+
+        Self {
+            entries: entries.len() as u64,
+            uncompressed_size,
+            archive_size,
+            compression_ratio,
+        }
+    }
+}
+
+/// Listing of an archive
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveInfo {
+    /// Format of the inner most layer
+    pub format: ArchiveFormat,
+    /// Formats of all layers from the inner most to the outer most
+    pub layers: Vec<ArchiveFormat>,
+    pub entries: Vec<Entry>,
+    pub summary: Summary,
+}
+
+impl ArchiveInfo {
+    /// Read the listing of the archive at `path` with the default limits
+    ///
+    /// All compression layers are detected and peeled off, see [NestedUnpacker].
+    pub fn read(path: &Path) -> Result<Self> {
+        Self::read_with(path, &Limits::default())
+    }
+
+    /// Read the listing of the archive at `path`
+    ///
+    /// Listing an archive decompresses it so the `limits` apply like for unpacking.
+    pub fn read_with(path: &Path, limits: &Limits) -> Result<Self> {
+        let unpacker = NestedUnpacker::open_with(path, limits)?;
+        let layers = unpacker.layers().to_vec();
+        let entries = unpacker.entries_with(limits)?;
+
+        let archive_size = fs::metadata(path)
+            .map_err(|source| Error::OpenArchiveFile {
+                source,
+                path: path.display().to_string(),
+            })?
+            .len();
+
+        Ok(Self::new(layers, entries, archive_size))
+    }
+
+    /// Read the listing of an archive from a stream like stdin with the default limits
+    pub fn from_reader<R>(reader: R) -> Result<Self>
+    where
+        R: Read + 'static,
+    {
+        Self::from_reader_with(reader, &Limits::default())
+    }
+
+    /// Read the listing of an archive from a stream like stdin
+    ///
+    /// All formats are detected from the content and a single compressed file without a name
+    /// in its header is listed as `archive`. The stream is read to its end to report its size.
+    pub fn from_reader_with<R>(reader: R, limits: &Limits) -> Result<Self>
+    where
+        R: Read + 'static,
+    {
+        let counted = Counted {
+            reader: Rc::new(RefCell::new(reader)),
+            count: Rc::new(Cell::new(0)),
+        };
+
+        let unpacker = NestedUnpacker::from_reader_with(counted.clone(), limits)?;
+        let layers = unpacker.layers().to_vec();
+        let entries = unpacker.entries_with(limits)?;
+
+        // listing stops at the end of the archive, padding after it still counts
+        io::copy(&mut counted.clone(), &mut io::sink()).map_err(|source| {
+            Error::ReadArchiveFile {
+                source,
+                path: String::from("-"),
+            }
+        })?;
+
+        Ok(Self::new(layers, entries, counted.count.get()))
+    }
+
+    fn new(layers: Vec<ArchiveFormat>, entries: Vec<Entry>, archive_size: u64) -> Self {
+        let summary = Summary::new(&entries, archive_size);
+
+        Self {
+            format: layers[0],
+            layers,
+            entries,
+            summary,
+        }
+    }
+}
+
+/// Reader shared with an unpacker which counts the bytes read from the stream
+struct Counted<R> {
+    reader: Rc<RefCell<R>>,
+    count: Rc<Cell<u64>>,
+}
+
+impl<R> Clone for Counted<R> {
+    fn clone(&self) -> Self {
+        Self {
+            reader: self.reader.clone(),
+            count: self.count.clone(),
+        }
+    }
+}
+
+impl<R> Read for Counted<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.reader.borrow_mut().read(buf)?;
+        self.count.set(self.count.get() + read as u64);
+
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn serialize_non_utf8_paths() -> Result<(), serde_json::Error> {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let entry = Entry {
+            path: PathBuf::from(OsStr::from_bytes(b"caf\xe9.txt")),
+            entry_type: EntryType::Symlink,
+            size: 0,
+            mode: 0o777,
+            uid: 0,
+            gid: 0,
+            user: None,
+            group: None,
+            mtime: 0,
+            link_target: Some(PathBuf::from(OsStr::from_bytes(b"\xff"))),
+        };
+
+        let value = serde_json::to_value(&entry)?;
+        assert_eq!(value["path"], "caf\u{fffd}.txt");
+        assert_eq!(value["link_target"], "\u{fffd}");
+
+        Ok(())
+    }
+}
@@ -1,16 +1,27 @@
 #![feature(backtrace)]
 
+pub mod atomic;
+pub mod chain;
+pub mod detect;
 pub mod error;
+pub mod extract;
+pub mod filter;
+pub mod info;
+pub mod nested;
 pub mod packer;
+pub mod rename;
+pub mod reproducible;
+pub mod walk;
 
-use std::{fmt, str::FromStr};
+use std::{fmt, ops::RangeInclusive, path::Path, str::FromStr};
 
 use error::Error;
+use serde::{Serialize, Serializer};
 
 #[derive(Debug, Clone, Copy)]
 pub enum CompressionLevel {
     Auto,
-    Numbered(u8),
+    Numbered(i32),
 }
 
 impl Default for CompressionLevel {
@@ -22,21 +33,17 @@ impl Default for CompressionLevel {
 impl FromStr for CompressionLevel {
     type Err = Error;
 
+    /// Parse `auto` or any numbered level
+    ///
+    /// The supported range depends on the format, see [ArchiveFormat::parse_level].
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "auto" => Ok(Self::Auto),
-            "0" => Ok(Self::Numbered(0)),
-            "1" => Ok(Self::Numbered(1)),
-            "2" => Ok(Self::Numbered(2)),
-            "3" => Ok(Self::Numbered(3)),
-            "4" => Ok(Self::Numbered(4)),
-            "5" => Ok(Self::Numbered(5)),
-            "6" => Ok(Self::Numbered(6)),
-            "7" => Ok(Self::Numbered(7)),
-            "8" => Ok(Self::Numbered(8)),
-            "9" => Ok(Self::Numbered(9)),
-            _ => Err(Error::InvalidCompressionLevel(s.to_owned())),
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(Self::Auto);
         }
+
+        s.parse()
+            .map(Self::Numbered)
+            .map_err(|_| Error::InvalidCompressionLevel(s.to_owned()))
     }
 }
 
@@ -49,12 +56,42 @@ impl fmt::Display for CompressionLevel {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Serialized as `"auto"` or the number of the level
+impl Serialize for CompressionLevel {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            CompressionLevel::Auto => serializer.serialize_str("auto"),
+            CompressionLevel::Numbered(n) => serializer.serialize_i32(*n),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ArchiveFormat {
     Tar,
     Zip,
     GZip,
-    Lzma,
+    Xz,
+    /// Legacy `.lzma` format written by LZMA Utils and `xz --format=lzma`
+    LzmaAlone,
+    Lzip,
+    Zstd,
+    Bzip2,
+}
+
+/// Role of a format in a chain of formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatKind {
+    /// Holds any number of files and directories
+    ///
+    /// Only tar and zip are implemented, 7z and cpio are not supported yet.
+    Container,
+    /// Compresses a single stream of data
+    Codec,
 }
 
 impl FromStr for ArchiveFormat {
@@ -65,7 +102,11 @@ impl FromStr for ArchiveFormat {
             "tar" => Ok(Self::Tar),
             "zip" => Ok(Self::Zip),
             "gzip" => Ok(Self::GZip),
-            "lzma" => Ok(Self::Lzma),
+            "xz" => Ok(Self::Xz),
+            "lzma" => Ok(Self::LzmaAlone),
+            "lzip" => Ok(Self::Lzip),
+            "zstd" => Ok(Self::Zstd),
+            "bzip2" => Ok(Self::Bzip2),
             _ => Err(Error::InvalidCompressionFormat(s.to_owned())),
         }
     }
@@ -77,14 +118,18 @@ impl fmt::Display for ArchiveFormat {
             ArchiveFormat::Tar => write!(f, "tar"),
             ArchiveFormat::Zip => write!(f, "zip"),
             ArchiveFormat::GZip => write!(f, "gzip"),
-            ArchiveFormat::Lzma => write!(f, "lzma"),
+            ArchiveFormat::Xz => write!(f, "xz"),
+            ArchiveFormat::LzmaAlone => write!(f, "lzma"),
+            ArchiveFormat::Lzip => write!(f, "lzip"),
+            ArchiveFormat::Zstd => write!(f, "zstd"),
+            ArchiveFormat::Bzip2 => write!(f, "bzip2"),
         }
     }
 }
 
 impl ArchiveFormat {
     pub const fn all() -> &'static [&'static str] {
-        &["tar", "zip", "gzip", "lzma"]
+        &["tar", "zip", "gzip", "xz", "lzma", "lzip", "zstd", "bzip2"]
     }
 
     pub fn try_from_ext(ext: &str) -> Vec<ArchiveFormat> {
@@ -92,18 +137,112 @@ impl ArchiveFormat {
             "tar" => vec![Self::Tar],
             "tgz" => vec![Self::Tar, Self::GZip],
             "gz" => vec![Self::GZip],
-            "xz" | "lz" | "lzma" => vec![Self::Lzma],
+            "xz" => vec![Self::Xz],
+            "txz" => vec![Self::Tar, Self::Xz],
+            "lzma" => vec![Self::LzmaAlone],
+            "tlz" => vec![Self::Tar, Self::Lzip],
+            "lz" => vec![Self::Lzip],
+            "zst" => vec![Self::Zstd],
+            "tzst" => vec![Self::Tar, Self::Zstd],
+            "bz2" => vec![Self::Bzip2],
+            "tbz" | "tbz2" => vec![Self::Tar, Self::Bzip2],
             "zip" => vec![Self::Zip],
             _ => Vec::new(),
         }
     }
 
+    /// Derive the archive formats from the extensions of a file name
+    ///
+    /// The formats are ordered from the inner most to the outer most.
+    pub fn from_path(path: &Path) -> Vec<ArchiveFormat> {
+        path.file_name()
+            .map(|name| {
+                name.to_string_lossy()
+                    .split('.')
+                    .flat_map(Self::try_from_ext)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
     pub fn as_ext(&self) -> &'static str {
         match self {
             ArchiveFormat::Tar => "tar",
             ArchiveFormat::Zip => "zip",
             ArchiveFormat::GZip => "gz",
-            ArchiveFormat::Lzma => "xz",
+            ArchiveFormat::Xz => "xz",
+            ArchiveFormat::LzmaAlone => "lzma",
+            ArchiveFormat::Lzip => "lz",
+            ArchiveFormat::Zstd => "zst",
+            ArchiveFormat::Bzip2 => "bz2",
+        }
+    }
+
+    pub fn kind(&self) -> FormatKind {
+        match self {
+            ArchiveFormat::Tar | ArchiveFormat::Zip => FormatKind::Container,
+            ArchiveFormat::GZip
+            | ArchiveFormat::Xz
+            | ArchiveFormat::LzmaAlone
+            | ArchiveFormat::Lzip
+            | ArchiveFormat::Zstd
+            | ArchiveFormat::Bzip2 => FormatKind::Codec,
         }
     }
+
+    /// Whether the format holds multiple entries rather than compressing a single stream
+    pub fn is_container(&self) -> bool {
+        self.kind() == FormatKind::Container
+    }
+
+    /// Range of the numbered compression levels supported by the format
+    ///
+    /// Returns `None` if the format does not compress.
+    pub fn level_range(&self) -> Option<RangeInclusive<i32>> {
+        match self {
+            ArchiveFormat::Tar => None,
+            ArchiveFormat::Zip
+            | ArchiveFormat::GZip
+            | ArchiveFormat::Xz
+            | ArchiveFormat::LzmaAlone
+            | ArchiveFormat::Lzip => Some(0..=9),
+            ArchiveFormat::Zstd => Some(zstd::compression_level_range()),
+            ArchiveFormat::Bzip2 => Some(1..=9),
+        }
+    }
+
+    /// Parse a compression level and check it is supported by the format
+    pub fn parse_level(&self, s: &str) -> Result<CompressionLevel, Error> {
+        let level = CompressionLevel::from_str(s)?;
+
+        match (level, self.level_range()) {
+            (CompressionLevel::Auto, _) => Ok(level),
+            (CompressionLevel::Numbered(_), None) => {
+                Err(Error::CompressionLevelNotSupported(*self))
+            }
+            (CompressionLevel::Numbered(n), Some(range)) if range.contains(&n) => Ok(level),
+            (CompressionLevel::Numbered(n), Some(range)) => {
+                Err(Error::CompressionLevelOutOfRange {
+                    format: *self,
+                    level: n,
+                    min: *range.start(),
+                    max: *range.end(),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// test compression levels serialize like they are written on the command line
+    #[test]
+    fn serialize_compression_level() -> Result<(), serde_json::Error> {
+        assert_eq!(serde_json::to_string(&CompressionLevel::Auto)?, "\"auto\"");
+        assert_eq!(serde_json::to_string(&CompressionLevel::Numbered(9))?, "9");
+
+        Ok(())
+    }
 }
@@ -2,27 +2,45 @@
 
 mod cli;
 mod macros;
+mod output;
 
 use std::{
-    fs::{self, File},
+    fs,
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
+    process,
     str::FromStr,
 };
 
 use clap::ArgMatches;
 use cli::args;
+use color_eyre::eyre::Report;
 use i18n_embed::{
     fluent::{fluent_language_loader, FluentLanguageLoader},
     DesktopLanguageRequester,
 };
 use itertools::Itertools;
 use log::{debug, error, info, trace, LevelFilter};
+use macros::fl;
 use once_cell::sync::Lazy;
+use output::OutputFormat;
 use rust_embed::RustEmbed;
 
 use brick::{
+    atomic::{self, AtomicFile},
+    chain,
     error::{Error, Result},
-    packer::{tar::TarPacker, Packer},
+    extract::{self, Conflict, Limits, UnpackOptions},
+    filter::{Filter, BRICKIGNORE, GITIGNORE},
+    info::ArchiveInfo,
+    nested::NestedUnpacker,
+    packer::{
+        self, bzip2::Bzip2Packer, gzip::GZipPacker, lzip::LzipPacker, tar::TarPacker,
+        xz::LzmaPacker, xz::XzPacker, zip::ZipPacker, zstd::ZstdPacker, Encoder, Packer, Unpacker,
+    },
+    rename::{Rename, Transform},
+    reproducible::Reproducible,
+    walk::WalkOptions,
     ArchiveFormat, CompressionLevel,
 };
 
@@ -34,6 +52,7 @@ pub(crate) static LANGUAGE_LOADER: Lazy<FluentLanguageLoader> = Lazy::new(|| {
     let loader = fluent_language_loader!();
     let requested_languages = DesktopLanguageRequester::requested_languages();
     let _result = i18n_embed::select(&loader, &Localizations, &requested_languages);
+    loader.set_use_isolating(false);
     loader
 });
 
@@ -59,17 +78,45 @@ fn main() -> color_eyre::eyre::Result<()> {
         .parse_default_env()
         .init();
 
-    match matches.subcommand() {
-        Some(("info", _sub_matches)) => todo!(),
-        Some(("pack", sub_matches)) => pack(sub_matches)?,
-        Some(("unpack", _sub_matches)) => todo!(),
+    // remove incomplete archives when interrupted
+    ctrlc::set_handler(|| {
+        atomic::remove_pending();
+        process::exit(130);
+    })?;
+
+    let result = match matches.subcommand() {
+        Some(("info", sub_matches)) => info(sub_matches),
+        Some(("pack", sub_matches)) => pack(sub_matches),
+        Some(("unpack", sub_matches)) => unpack(sub_matches),
         Some(_) => todo!(),
         None => todo!(),
+    };
+
+    if let Err(error) = result {
+        let exit_code = error.exit_code();
+        eprintln!("Error: {:?}", Report::from(error));
+        process::exit(exit_code);
     }
 
     Ok(())
 }
 
+fn info(sub_matches: &ArgMatches) -> Result<()> {
+    let input_path = sub_matches.value_of_t_or_exit::<PathBuf>(args::INPUT_PATH);
+
+    let info = if is_stdio(&input_path) {
+        ArchiveInfo::from_reader_with(io::stdin(), &limits(sub_matches))?
+    } else {
+        ArchiveInfo::read_with(&input_path, &limits(sub_matches))?
+    };
+
+    let output_format = sub_matches.value_of_t_or_exit::<OutputFormat>(args::OUTPUT_FORMAT);
+
+    output::write_info(io::stdout().lock(), &info, output_format)?;
+
+    Ok(())
+}
+
 fn pack(sub_matches: &ArgMatches) -> Result<()> {
     if sub_matches.occurrences_of(args::COMPRESSION_LEVEL)
         > sub_matches.occurrences_of(args::FORMAT)
@@ -78,141 +125,685 @@ fn pack(sub_matches: &ArgMatches) -> Result<()> {
         return Ok(());
     }
 
-    let input_paths = sub_matches.values_of_t_or_exit::<PathBuf>(args::INPUT_PATHS);
-    let mut output_path = sub_matches.value_of_t_or_exit::<PathBuf>(args::OUTPUT_PATH);
+    let mut input_paths = if sub_matches.is_present(args::INPUT_PATHS) {
+        sub_matches.values_of_t_or_exit::<PathBuf>(args::INPUT_PATHS)
+    } else {
+        Vec::new()
+    };
+
+    // long lists of input paths are read from a file to avoid the limits of the command line
+    let files_from = sub_matches.value_of_os(args::FILES_FROM).map(Path::new);
 
-    if output_path.is_relative() {
-        output_path = Path::new(".").join(output_path);
+    if let Some(files_from) = files_from {
+        if is_stdio(files_from) && input_paths.iter().any(|path| is_stdio(path)) {
+            return Err(Error::StdinUsedTwice);
+        }
+
+        input_paths.extend(read_file_list(
+            files_from,
+            sub_matches.is_present(args::NULL),
+        )?);
+    }
+
+    if input_paths.is_empty() {
+        return Err(Error::NoInputFiles);
     }
 
-    if sub_matches.occurrences_of(args::FORMAT_GROUP) > 0 {
-        if let Some(values) = sub_matches.grouped_values_of(args::FORMAT_GROUP) {
-            // derive archive format and level from arguments
-            debug!("Deriving archive format and level from arguments");
-
-            let formats = values
-                .map(|vec| vec.first().copied())
-                .tuples()
-                .map(|(format, level)| {
-                    Result::<(ArchiveFormat, CompressionLevel)>::Ok((
-                        format
-                            .map(ArchiveFormat::from_str)
-                            .transpose()?
-                            .ok_or(Error::MissingCompressionFormat)?,
-                        level
-                            .map(CompressionLevel::from_str)
-                            .transpose()?
-                            .unwrap_or_default(),
-                    ))
-                })
-                .collect::<Result<Vec<_>>>()?;
-
-            do_pack(input_paths, formats, output_path)?;
+    let stdin_used = input_paths.iter().any(|path| is_stdio(path))
+        || matches!(files_from, Some(path) if is_stdio(path));
+
+    let long_window_log = sub_matches
+        .is_present(args::LONG_WINDOW)
+        .then(|| sub_matches.value_of_t_or_exit::<u32>(args::LONG_WINDOW));
+
+    let (mut output_path, formats) = if sub_matches.occurrences_of(args::FORMAT) > 0 {
+        // derive archive format and level from arguments
+        debug!("Deriving archive format and level from arguments");
+
+        let formats = formats_from_args(sub_matches)?;
+
+        let output_path = if sub_matches.is_present(args::OUTPUT_PATH) {
+            sub_matches.value_of_t_or_exit::<PathBuf>(args::OUTPUT_PATH)
+        } else if input_paths.iter().any(|path| is_stdio(path)) {
+            // like gzip data compressed from stdin is written to stdout
+            PathBuf::from(STDIO)
+        } else if matches!(formats.first(), Some((format, _)) if !format.is_container()) {
+            // like gzip a single compressed file is written next to its input
+            input_paths
+                .first()
+                .cloned()
+                .unwrap_or_else(|| PathBuf::from("archive"))
         } else {
-            unreachable!("");
+            // derive archive name from the first input path
+            input_paths
+                .first()
+                .map(|path| default_name(path))
+                .unwrap_or_else(|| PathBuf::from("archive"))
+        };
+
+        if is_stdio(&output_path) {
+            (output_path, formats)
+        } else {
+            (with_extensions(output_path, &formats), formats)
         }
     } else {
         // derive archive format from output file name
         debug!("Deriving archive format from output file name");
 
-        let path = sub_matches.value_of_t_or_exit::<PathBuf>(args::OUTPUT_PATH);
+        let output_path = sub_matches.value_of_t_or_exit::<PathBuf>(args::OUTPUT_PATH);
 
-        let formats = path
-            .file_name()
-            .unwrap()
-            .to_string_lossy()
-            .split('.')
-            .flat_map(ArchiveFormat::try_from_ext)
+        if is_stdio(&output_path) {
+            return Err(Error::StdoutRequiresFormat);
+        }
+
+        let formats = ArchiveFormat::from_path(&output_path)
+            .into_iter()
             .map(|format| (format, CompressionLevel::Auto))
             .collect_vec();
 
-        do_pack(input_paths, formats, output_path)?;
+        if formats.is_empty() {
+            return Err(Error::UnknownArchiveFormat(
+                output_path.display().to_string(),
+            ));
+        }
+
+        (output_path, formats)
+    };
+
+    if output_path.is_relative() && !is_stdio(&output_path) {
+        output_path = Path::new(".").join(output_path);
+    }
+
+    // like gzip only a single compressed file is replaced by its archive, unless --keep is
+    // given or it is written to stdout
+    let delete_input = match formats.first() {
+        Some((container, _)) if container.is_container() => {
+            if sub_matches.is_present(args::DELETE_INPUT) {
+                return Err(Error::DeleteInputNotSupported(*container));
+            }
+
+            false
+        }
+        _ => !sub_matches.is_present(args::KEEP) && !is_stdio(&output_path),
+    };
+
+    let parents = sub_matches.is_present(args::PARENTS);
+    let overwrite = if sub_matches.is_present(args::OVERWRITE) {
+        Overwrite::Always
+    } else if sub_matches.is_present(args::NO_CLOBBER) {
+        Overwrite::Never
+    } else {
+        Overwrite::Ask
+    };
+
+    if !is_stdio(&output_path) && output_path.exists() {
+        check_overwrite(&output_path, overwrite, stdin_used)?;
+    }
+
+    let reproducible = if sub_matches.is_present(args::REPRODUCIBLE) {
+        Some(Reproducible::from_env()?)
+    } else {
+        None
+    };
+
+    let inputs = Inputs {
+        paths: &input_paths,
+        walk: walk_options(sub_matches)?,
+        rename: Rename {
+            prefix: sub_matches.value_of_os(args::PREFIX).map(PathBuf::from),
+            ..rename(sub_matches)
+        },
+        reproducible,
+    };
+
+    do_pack(&inputs, formats, &output_path, long_window_log, parents)?;
+
+    if delete_input {
+        for path in input_paths.iter().filter(|path| !is_stdio(path)) {
+            debug!("Removing input file {}", path.display());
+
+            fs::remove_file(path).map_err(|source| Error::RemoveInputFile {
+                source,
+                path: path.display().to_string(),
+            })?;
+        }
     }
 
     Ok(())
 }
 
-fn do_pack(
-    input_paths: Vec<PathBuf>,
-    formats: Vec<(ArchiveFormat, CompressionLevel)>,
-    output_path: PathBuf,
-) -> Result<()> {
-    let mut iter = formats.into_iter();
-    let mut target_name = output_path
-        .file_name()
-        .and_then(|s| {
-            s.to_string_lossy()
-                .split_once('.')
-                .map(|(left, _)| left.to_string())
+/// Limits for decompressing an archive, the defaults are replaced by the given options
+fn limits(sub_matches: &ArgMatches) -> Limits {
+    if sub_matches.is_present(args::NO_LIMITS) {
+        return Limits::unlimited();
+    }
+
+    let size = |name| {
+        sub_matches
+            .value_of(name)
+            .map(|value| extract::parse_size(value).expect("validated by clap"))
+    };
+    let number = |name| {
+        sub_matches
+            .is_present(name)
+            .then(|| sub_matches.value_of_t_or_exit::<u64>(name))
+    };
+
+    let defaults = Limits::default();
+
+    Limits {
+        max_total_size: size(args::MAX_SIZE).or(defaults.max_total_size),
+        max_entries: number(args::MAX_ENTRIES).or(defaults.max_entries),
+        max_entry_size: size(args::MAX_ENTRY_SIZE).or(defaults.max_entry_size),
+        max_ratio: number(args::MAX_RATIO).or(defaults.max_ratio),
+        max_layers: number(args::MAX_LAYERS)
+            .map(|layers| layers as usize)
+            .or(defaults.max_layers),
+    }
+}
+
+fn walk_options(sub_matches: &ArgMatches) -> Result<WalkOptions> {
+    let mut filter = Filter::default();
+
+    if sub_matches.is_present(args::GITIGNORE) {
+        filter.ignore_files.push(GITIGNORE.into());
+    }
+    if sub_matches.is_present(args::NO_BRICKIGNORE) {
+        filter.ignore_files.retain(|name| name != BRICKIGNORE);
+    }
+
+    if sub_matches.is_present(args::EXCLUDE_VCS) {
+        filter.exclude_vcs();
+    }
+    for path in sub_matches
+        .values_of_os(args::EXCLUDE_FROM)
+        .unwrap_or_default()
+    {
+        filter.exclude_from(Path::new(path))?;
+    }
+
+    // later patterns override earlier ones, so excludes and includes keep their order
+    let patterns = |name| {
+        sub_matches
+            .indices_of(name)
+            .unwrap_or_default()
+            .zip(sub_matches.values_of(name).unwrap_or_default())
+    };
+    let excludes = patterns(args::EXCLUDE).map(|(index, glob)| (index, glob, false));
+    let includes = patterns(args::INCLUDE).map(|(index, glob)| (index, glob, true));
+
+    for (_, glob, include) in excludes
+        .chain(includes)
+        .sorted_by_key(|(index, _, _)| *index)
+    {
+        if include {
+            filter.include(glob);
+        } else {
+            filter.exclude(glob);
+        }
+    }
+
+    Ok(WalkOptions {
+        follow_symlinks: sub_matches.is_present(args::FOLLOW_SYMLINKS),
+        same_file_system: sub_matches.is_present(args::ONE_FILE_SYSTEM),
+        max_depth: sub_matches
+            .is_present(args::RECURSION_DEPTH)
+            .then(|| sub_matches.value_of_t_or_exit::<usize>(args::RECURSION_DEPTH)),
+        skip_special: !sub_matches.is_present(args::SPECIAL_FILES),
+        filter,
+        ..WalkOptions::default()
+    })
+}
+
+fn rename(sub_matches: &ArgMatches) -> Rename {
+    Rename {
+        strip_components: if sub_matches.is_present(args::STRIP_COMPONENTS) {
+            sub_matches.value_of_t_or_exit(args::STRIP_COMPONENTS)
+        } else {
+            0
+        },
+        transforms: if sub_matches.is_present(args::TRANSFORM) {
+            sub_matches.values_of_t_or_exit::<Transform>(args::TRANSFORM)
+        } else {
+            Vec::new()
+        },
+        prefix: None,
+    }
+}
+
+/// What to do when the output file exists already
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Overwrite {
+    Always,
+    Never,
+    /// Prompt if stdin is a terminal, otherwise refuse
+    Ask,
+}
+
+/// Decide whether the existing file at `path` may be replaced
+fn check_overwrite(path: &Path, overwrite: Overwrite, stdin_used: bool) -> Result<()> {
+    let refuse = || Err(Error::OutputExists(path.display().to_string()));
+
+    match overwrite {
+        Overwrite::Always => Ok(()),
+        Overwrite::Never => refuse(),
+        // stdin can not answer a prompt if it is not a terminal or holds the data to pack
+        Overwrite::Ask if !atty::is(atty::Stream::Stdin) || stdin_used => refuse(),
+        Overwrite::Ask => {
+            eprint!(
+                "{} ",
+                fl!("pack-overwrite-prompt", path = path.display().to_string())
+            );
+
+            let mut answer = String::new();
+            io::stdin()
+                .read_line(&mut answer)
+                .map_err(Error::ReadStdin)?;
+
+            if matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+                Ok(())
+            } else {
+                refuse()
+            }
+        }
+    }
+}
+
+/// Path used for stdin and stdout
+const STDIO: &str = "-";
+
+/// Read the paths listed in the file `path` separated by newlines or with `null` by NUL bytes
+///
+/// `-` reads the list from stdin. Listed paths are always files, a listed `-` is not stdin.
+fn read_file_list(path: &Path, null: bool) -> Result<Vec<PathBuf>> {
+    debug!("Reading input paths from {}", path.display());
+
+    let map_err = |source| Error::ReadFileList {
+        source,
+        path: path.display().to_string(),
+    };
+
+    let list = if is_stdio(path) {
+        let mut list = Vec::new();
+        io::stdin().lock().read_to_end(&mut list).map_err(map_err)?;
+        list
+    } else {
+        fs::read(path).map_err(map_err)?
+    };
+
+    let separator = if null { b'\0' } else { b'\n' };
+
+    Ok(list
+        .split(|byte| *byte == separator)
+        .map(|name| {
+            // lists written on windows end their lines with \r\n
+            if null {
+                name
+            } else {
+                name.strip_suffix(b"\r").unwrap_or(name)
+            }
+        })
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            let path = path_from_bytes(name);
+
+            if is_stdio(&path) {
+                Path::new(".").join(path)
+            } else {
+                path
+            }
         })
+        .collect())
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    OsStr::from_bytes(bytes).into()
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    String::from_utf8_lossy(bytes).into_owned().into()
+}
+
+/// Whether `path` stands for stdin or stdout
+fn is_stdio(path: &Path) -> bool {
+    path == Path::new(STDIO)
+}
+
+/// Name of an archive packing `path` without extensions
+///
+/// Paths without a name like `.` or `..` are resolved to the name of the directory they
+/// point to.
+fn default_name(path: &Path) -> PathBuf {
+    path.file_name()
+        .map(PathBuf::from)
+        .or_else(|| {
+            path.canonicalize()
+                .ok()
+                .and_then(|path| path.file_name().map(PathBuf::from))
+        })
+        .unwrap_or_else(|| PathBuf::from("archive"))
+}
+
+/// Append the extensions of `formats` to `path` unless its name already ends with them
+///
+/// `huge.log` packed as zstd becomes `huge.log.zst`, `directory` packed as tar and gzip
+/// becomes `directory.tar.gz`.
+fn with_extensions(path: PathBuf, formats: &[(ArchiveFormat, CompressionLevel)]) -> PathBuf {
+    let extensions = formats
+        .iter()
+        .map(|(format, _)| format!(".{}", format.as_ext()))
+        .collect::<String>();
+
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
         .unwrap_or_else(|| String::from("archive"));
 
-    trace!("Archive name: {target_name}");
+    if name.ends_with(&extensions) && name.len() > extensions.len() {
+        path
+    } else {
+        path.with_file_name(format!("{name}{extensions}"))
+    }
+}
+
+/// Collect formats and their compression levels from the command line
+///
+/// A compression level applies to the format preceding it.
+fn formats_from_args(sub_matches: &ArgMatches) -> Result<Vec<(ArchiveFormat, CompressionLevel)>> {
+    let mut formats = sub_matches
+        .values_of(args::FORMAT)
+        .into_iter()
+        .flatten()
+        .zip(sub_matches.indices_of(args::FORMAT).into_iter().flatten())
+        .map(|(format, index)| {
+            Ok((
+                index,
+                ArchiveFormat::from_str(format)?,
+                CompressionLevel::default(),
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if sub_matches.occurrences_of(args::COMPRESSION_LEVEL) > 0 {
+        let levels = sub_matches
+            .values_of(args::COMPRESSION_LEVEL)
+            .into_iter()
+            .flatten()
+            .zip(
+                sub_matches
+                    .indices_of(args::COMPRESSION_LEVEL)
+                    .into_iter()
+                    .flatten(),
+            );
+
+        for (level, level_index) in levels {
+            let (_, format, format_level) = formats
+                .iter_mut()
+                .rev()
+                .find(|(format_index, _, _)| *format_index < level_index)
+                .ok_or(Error::MissingCompressionFormat)?;
+
+            *format_level = format.parse_level(level)?;
+        }
+    }
 
-    if let Some((format, level)) = iter.next() {
-        debug!("Packing inner most archive");
+    Ok(formats
+        .into_iter()
+        .map(|(_, format, level)| (format, level))
+        .collect())
+}
+
+/// Files to pack and how to add them to the archive
+struct Inputs<'a> {
+    paths: &'a [PathBuf],
+    walk: WalkOptions,
+    rename: Rename,
+    /// Normalize the metadata of entries, see [Reproducible]
+    reproducible: Option<Reproducible>,
+}
+
+fn do_pack(
+    inputs: &Inputs,
+    formats: Vec<(ArchiveFormat, CompressionLevel)>,
+    output_path: &Path,
+    long_window_log: Option<u32>,
+    parents: bool,
+) -> Result<()> {
+    // check the chain before creating any file
+    chain::validate(
+        &formats.iter().map(|(format, _)| *format).collect_vec(),
+        inputs.paths,
+    )?;
+
+    let ((format, level), outer_formats) = formats
+        .split_first()
+        .map(|(inner, outer)| (*inner, outer))
+        .ok_or(Error::MissingCompressionFormat)?;
+
+    if format.is_container() && inputs.paths.iter().any(|path| is_stdio(path)) {
+        return Err(Error::ContainerFromStdin(format));
+    }
 
-        target_name.push('.');
-        target_name.push_str(format.as_ext());
+    info!(
+        "Packing {:?} as {format} with compression level {level} to {}",
+        inputs.paths,
+        output_path.display()
+    );
 
-        pack_files(&input_paths, format, level, &target_name)?;
+    if is_stdio(output_path) {
+        return write_archive(io::stdout(), inputs, &formats, long_window_log);
     }
 
-    for (format, level) in iter {
-        let new_target_name = format!("{target_name}.{}", format.as_ext());
+    let directory = output_path
+        .parent()
+        .filter(|path| !path.as_os_str().is_empty());
 
-        pack_files(
-            &[PathBuf::from(&target_name)],
-            format,
-            level,
-            &new_target_name,
-        )?;
+    if let Some(directory) = directory.filter(|path| !path.exists()) {
+        if !parents {
+            return Err(Error::MissingOutputDirectory(
+                directory.display().to_string(),
+            ));
+        }
+
+        debug!("Creating output directory {}", directory.display());
 
-        fs::remove_file(&target_name).map_err(|source| Error::RemoveOldArchive {
+        fs::create_dir_all(directory).map_err(|source| Error::CreateOutputDirectory {
             source,
-            path: target_name.to_string(),
+            path: directory.display().to_string(),
         })?;
+    }
+
+    trace!("Creating archive file {}", output_path.display());
+
+    // the archive is written to a temporary file and only replaces the output when complete
+    let file = AtomicFile::create(output_path)?;
 
-        target_name = new_target_name;
+    if outer_formats.is_empty() && format == ArchiveFormat::Zip {
+        // zip archives need a seekable writer, without outer layers this is the file itself
+        add_paths(
+            ZipPacker::new(file.as_file(), level)?
+                .rename(inputs.rename.clone())
+                .reproducible(inputs.reproducible),
+            inputs,
+        )?;
+    } else {
+        write_archive(file.as_file(), inputs, &formats, long_window_log)?;
     }
 
-    Ok(())
+    file.commit()
 }
 
-fn pack_files<OutputPath>(
-    paths: &[PathBuf],
+/// Pack `inputs` as `formats` into `output`
+///
+/// All formats after the first are streamed through a chain of encoders.
+fn write_archive<W>(
+    output: W,
+    inputs: &Inputs,
+    formats: &[(ArchiveFormat, CompressionLevel)],
+    long_window_log: Option<u32>,
+) -> Result<()>
+where
+    W: Write,
+{
+    let ((format, level), outer_formats) = formats
+        .split_first()
+        .map(|(inner, outer)| (*inner, outer))
+        .ok_or(Error::MissingCompressionFormat)?;
+
+    let mut writer: Box<dyn Encoder + '_> = Box::new(BufWriter::new(output));
+
+    // chain the encoders of all outer layers, the outer most writes to the output
+    for (format, level) in outer_formats.iter().rev() {
+        debug!("Compressing as {format} with compression level {level}");
+
+        writer = packer::encoder(writer, *format, *level, long_window_log)?;
+    }
+
+    if inputs.paths.iter().any(|path| is_stdio(path)) {
+        // stdin is a single stream without metadata, the inner most codec is an encoder too
+        writer = packer::encoder(writer, format, level, long_window_log)?;
+        io::copy(&mut io::stdin().lock(), &mut writer)
+            .map_err(|source| Error::packer(format, source))?;
+
+        return writer.finish();
+    }
+
+    pack_files(inputs, format, level, long_window_log, &mut writer)?;
+
+    writer.finish()
+}
+
+/// Pack `inputs` as the inner most `format` into `writer`
+fn pack_files<W>(
+    inputs: &Inputs,
     format: ArchiveFormat,
     level: CompressionLevel,
-    output_path: OutputPath,
+    long_window_log: Option<u32>,
+    writer: &mut W,
 ) -> Result<()>
 where
-    OutputPath: AsRef<Path>,
+    W: Write,
 {
-    trace!("Creating archive file {}", output_path.as_ref().display());
+    match format {
+        ArchiveFormat::Tar => add_paths(
+            TarPacker::new(writer)?
+                .rename(inputs.rename.clone())
+                .reproducible(inputs.reproducible),
+            inputs,
+        ),
+        ArchiveFormat::Zip => {
+            // zip archives need a seekable writer, buffer them in a temporary file
+            let map_err = |source| Error::BufferNestedArchive { format, source };
+
+            let mut buffer = tempfile::tempfile().map_err(map_err)?;
+            add_paths(
+                ZipPacker::new(&buffer, level)?
+                    .rename(inputs.rename.clone())
+                    .reproducible(inputs.reproducible),
+                inputs,
+            )?;
+
+            buffer.seek(SeekFrom::Start(0)).map_err(map_err)?;
+            io::copy(&mut buffer, writer).map_err(map_err)?;
+
+            Ok(())
+        }
+        ArchiveFormat::GZip => add_paths(
+            GZipPacker::new(writer, level)?.reproducible(inputs.reproducible),
+            inputs,
+        ),
+        ArchiveFormat::Xz => add_paths(XzPacker::new(writer, level)?, inputs),
+        ArchiveFormat::LzmaAlone => add_paths(LzmaPacker::new(writer, level)?, inputs),
+        ArchiveFormat::Lzip => add_paths(LzipPacker::new(writer, level)?, inputs),
+        ArchiveFormat::Bzip2 => add_paths(Bzip2Packer::new(writer, level)?, inputs),
+        ArchiveFormat::Zstd => add_paths(
+            ZstdPacker::new(writer, level)?.long_distance_matching(long_window_log),
+            inputs,
+        ),
+    }
+}
 
-    let file = File::create(&output_path).map_err(|source| Error::CreateArchiveFile {
-        source,
-        path: output_path.as_ref().display().to_string(),
-    })?;
+fn add_paths<P>(mut packer: P, inputs: &Inputs) -> Result<()>
+where
+    P: Packer,
+{
+    for path in inputs.paths {
+        packer.add_path_with(path, &inputs.walk)?;
+    }
 
-    info!(
-        "Packing {:?} as {format} with compression level {level} to {}",
-        paths,
-        output_path.as_ref().display()
-    );
+    packer.finish()
+}
 
-    let mut packer = match format {
-        ArchiveFormat::Tar => TarPacker::new(&file)?,
-        ArchiveFormat::Zip => todo!(),
-        ArchiveFormat::GZip => todo!(),
-        ArchiveFormat::Lzma => todo!(),
+fn unpack(sub_matches: &ArgMatches) -> Result<()> {
+    let input_path = sub_matches.value_of_t_or_exit::<PathBuf>(args::INPUT_PATH);
+    let output_path = sub_matches.value_of_t_or_exit::<PathBuf>(args::OUTPUT_DIRECTORY);
+
+    // detect archive formats from content and input file name
+    debug!("Detecting archive formats of input file");
+
+    let limits = limits(sub_matches);
+
+    let unpacker = if is_stdio(&input_path) {
+        NestedUnpacker::from_reader_with(io::stdin(), &limits)?
+    } else {
+        NestedUnpacker::open_with(&input_path, &limits)?
     };
 
-    for path in paths {
-        packer.add_path(path)?;
+    // like gzip only a single compressed file can be replaced by its content
+    let delete_input = sub_matches.is_present(args::DELETE_INPUT);
+    let inner_format = unpacker.layers()[0];
+
+    if delete_input && inner_format.is_container() {
+        return Err(Error::DeleteInputNotSupported(inner_format));
     }
 
-    packer.finish()?;
+    info!(
+        "Unpacking {} as {} to {}",
+        input_path.display(),
+        unpacker.layers().iter().join(" in "),
+        output_path.display()
+    );
+
+    if is_stdio(&output_path) {
+        let mut stdout = io::stdout().lock();
+        unpacker.unpack_to_writer(&mut stdout)?;
+        stdout.flush().map_err(Error::WriteOutput)?;
+    } else {
+        let conflict = if sub_matches.is_present(args::SKIP_EXISTING) {
+            Conflict::Skip
+        } else if sub_matches.is_present(args::KEEP_NEWER) {
+            Conflict::KeepNewer
+        } else if sub_matches.is_present(args::RENAME) {
+            Conflict::Rename
+        } else if sub_matches.is_present(args::BACKUP) {
+            Conflict::Backup
+        } else {
+            Conflict::Overwrite
+        };
+
+        let options = UnpackOptions {
+            conflict,
+            unsafe_paths: sub_matches.is_present(args::UNSAFE_PATHS),
+            preserve_permissions: sub_matches.is_present(args::PRESERVE_PERMISSIONS),
+            limits,
+            rename: rename(sub_matches),
+        };
+
+        unpacker.unpack_with(&output_path, &options)?;
+    }
+
+    if delete_input && !is_stdio(&input_path) {
+        debug!("Removing input file {}", input_path.display());
+
+        fs::remove_file(&input_path).map_err(|source| Error::RemoveInputFile {
+            source,
+            path: input_path.display().to_string(),
+        })?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,690 @@
+//! Unpack archives nested in any number of compression layers
+//!
+//! The layers are taken from the extensions of the file name if it agrees with the content
+//! of the file and detected from the content otherwise. Every layer is decompressed while
+//! reading so no intermediate files are written. Only a zip
+//! archive inside of a compressed stream is buffered in a temporary file since it needs to
+//! be seekable.
+
+use std::{
+    cell::Cell,
+    ffi::{OsStr, OsString},
+    fs::File,
+    io::{self, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::Path,
+    rc::Rc,
+};
+
+use log::{debug, warn};
+
+use crate::{
+    detect::{self, PeekReader},
+    error::{Error, Result},
+    extract::{check_limit, check_ratio, Limit, Limits, UnpackOptions},
+    info::Entry,
+    packer::{
+        decoder, modified_time, stream_entry, tar::TarUnpacker, unpack_stream, zip::ZipUnpacker,
+        Unpacker,
+    },
+    ArchiveFormat,
+};
+
+/// Innermost content of a nested archive
+enum Content {
+    Tar(Box<dyn Read>),
+    Zip(File),
+    /// A single file which was only compressed
+    Stream {
+        reader: Box<dyn Read>,
+        name: OsString,
+        mtime: Option<u64>,
+    },
+}
+
+/// Measures the compressed input and the decompressed content against the limits
+struct Meter {
+    limits: Limits,
+    compressed: Cell<u64>,
+    uncompressed: Cell<u64>,
+    /// Limit which stopped reading, the readers can only return an [io::Error]
+    exceeded: Cell<Option<Limit>>,
+}
+
+impl Meter {
+    fn new(limits: &Limits) -> Rc<Self> {
+        Rc::new(Self {
+            limits: *limits,
+            compressed: Cell::new(0),
+            uncompressed: Cell::new(0),
+            exceeded: Cell::new(None),
+        })
+    }
+
+    /// Replace an error caused by reading past a limit with the exceeded limit
+    fn error(&self, error: Error) -> Error {
+        match self.exceeded.get() {
+            Some(limit) => Error::LimitExceeded(limit),
+            None => error,
+        }
+    }
+}
+
+/// Counts the bytes read from the archive
+struct Compressed<R> {
+    reader: R,
+    meter: Rc<Meter>,
+}
+
+impl<R> Read for Compressed<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        let compressed = &self.meter.compressed;
+        compressed.set(compressed.get() + read as u64);
+
+        Ok(read)
+    }
+}
+
+/// Counts the decompressed bytes of the content and stops at the limits
+struct Decompressed<R> {
+    reader: R,
+    meter: Rc<Meter>,
+}
+
+impl<R> Read for Decompressed<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.reader.read(buf)?;
+
+        let meter = &self.meter;
+        let uncompressed = meter.uncompressed.get() + read as u64;
+        meter.uncompressed.set(uncompressed);
+
+        let checked = check_limit(meter.limits.max_total_size, uncompressed, Limit::TotalSize)
+            .and_then(|_| check_ratio(&meter.limits, uncompressed, meter.compressed.get()));
+
+        match checked {
+            Err(Error::LimitExceeded(limit)) => {
+                meter.exceeded.set(Some(limit));
+
+                Err(io::Error::new(ErrorKind::InvalidData, limit.to_string()))
+            }
+            _ => Ok(read),
+        }
+    }
+}
+
+pub struct NestedUnpacker {
+    /// Formats of all layers from the inner most to the outer most
+    layers: Vec<ArchiveFormat>,
+    content: Content,
+    meter: Rc<Meter>,
+}
+
+impl NestedUnpacker {
+    /// Open an archive and peel off all compression layers with the default limits
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::open_with(path, &Limits::default())
+    }
+
+    /// Open an archive and peel off all compression layers
+    ///
+    /// The depth, decompressed size and compression ratio of the layers are checked against
+    /// `limits` while reading, the entries against the limits given to
+    /// [Unpacker::unpack_with] or [Unpacker::entries_with].
+    pub fn open_with(path: &Path, limits: &Limits) -> Result<Self> {
+        debug!("Opening nested archive `{}`", path.display());
+
+        let detected = detect::detect_path(path)?;
+        // the name only describes the layers if its outer most format matches the content
+        let from_name = if detected == ArchiveFormat::from_path(path) {
+            detected.clone()
+        } else {
+            Vec::new()
+        };
+
+        let file = File::open(path).map_err(|source| Error::OpenArchiveFile {
+            source,
+            path: path.display().to_string(),
+        })?;
+
+        // a zip archive which is not compressed can be read without buffering it
+        if detected.last() == Some(&ArchiveFormat::Zip) {
+            return Ok(Self {
+                layers: vec![ArchiveFormat::Zip],
+                content: Content::Zip(file),
+                meter: Meter::new(limits),
+            });
+        }
+
+        let name = path
+            .file_name()
+            .map(ToOwned::to_owned)
+            .unwrap_or_else(|| OsString::from("archive"));
+        let mtime = modified_time(&file);
+
+        Self::peel(
+            Box::new(file),
+            path.display().to_string(),
+            from_name,
+            Some(name),
+            mtime,
+            limits,
+        )
+    }
+
+    /// Read an archive from a stream like stdin and peel off all compression layers with the
+    /// default limits
+    ///
+    /// All formats are detected from the content.
+    pub fn from_reader<R>(reader: R) -> Result<Self>
+    where
+        R: Read + 'static,
+    {
+        Self::from_reader_with(reader, &Limits::default())
+    }
+
+    /// Read an archive from a stream like stdin and peel off all compression layers
+    ///
+    /// See [NestedUnpacker::open_with] for how `limits` are applied.
+    pub fn from_reader_with<R>(reader: R, limits: &Limits) -> Result<Self>
+    where
+        R: Read + 'static,
+    {
+        debug!("Reading nested archive from stream");
+
+        Self::peel(
+            Box::new(reader),
+            String::from("-"),
+            Vec::new(),
+            None,
+            None,
+            limits,
+        )
+    }
+
+    /// Detect and decompress layers until a container or the compressed file is reached
+    ///
+    /// `path` is only used in errors and warnings. `from_name` holds the formats derived from
+    /// the file name from the inner most to the outer most. If it is not empty the name
+    /// decides every layer, so a `report.docx.zst` restores `report.docx` instead of
+    /// unpacking the document as zip. Otherwise all layers are detected from the content.
+    fn peel(
+        reader: Box<dyn Read>,
+        path: String,
+        mut from_name: Vec<ArchiveFormat>,
+        mut name: Option<OsString>,
+        mut mtime: Option<u64>,
+        limits: &Limits,
+    ) -> Result<Self> {
+        let meter = Meter::new(limits);
+        let mut reader: Box<dyn Read> = Box::new(Compressed {
+            reader,
+            meter: meter.clone(),
+        });
+        let mut layers = Vec::new();
+        let by_name = !from_name.is_empty();
+
+        let content = loop {
+            let peek = PeekReader::new(reader).map_err(|source| match layers.last() {
+                Some(format) => Error::unpacker(*format, source),
+                None => Error::ReadArchiveFile {
+                    source,
+                    path: path.clone(),
+                },
+            })?;
+
+            let detected = peek.format();
+
+            // every layer consumes one format of the file name
+            let format = if by_name {
+                let expected = from_name.pop();
+
+                // the outer most layer is already checked by detect_path
+                if let (Some(expected), Some(detected), Some(outer)) =
+                    (expected, detected, layers.last())
+                {
+                    if expected != detected {
+                        warn!("{path} is named like a {expected} archive inside {outer} but contains {detected}");
+                    }
+                }
+
+                expected
+            } else {
+                detected
+            };
+
+            let format = match format {
+                None if layers.is_empty() => return Err(Error::UnknownArchiveFormat(path)),
+                None => {
+                    break Content::Stream {
+                        reader: Box::new(Decompressed {
+                            reader: peek,
+                            meter: meter.clone(),
+                        }),
+                        name: name.unwrap_or_else(|| OsString::from("archive")),
+                        mtime,
+                    }
+                }
+                Some(format) => format,
+            };
+
+            match limits.max_layers {
+                Some(max) if layers.len() >= max => {
+                    return Err(Error::LimitExceeded(Limit::Layers(max)))
+                }
+                _ => layers.push(format),
+            }
+
+            let content = Decompressed {
+                reader: peek,
+                meter: meter.clone(),
+            };
+
+            match format {
+                ArchiveFormat::Tar => break Content::Tar(Box::new(content)),
+                ArchiveFormat::Zip => {
+                    break Content::Zip(buffer(content).map_err(|error| meter.error(error))?)
+                }
+                codec => {
+                    debug!("Peeling {codec} layer");
+
+                    let (decoder, header) = decoder(codec, content.reader)?;
+                    name = header
+                        .name
+                        .or_else(|| name.map(|name| decompressed_name(&name, codec)));
+                    mtime = header.mtime.or(mtime);
+                    reader = decoder;
+                }
+            }
+        };
+
+        layers.reverse();
+
+        Ok(Self {
+            layers,
+            content,
+            meter,
+        })
+    }
+
+    /// Formats of all layers from the inner most to the outer most
+    pub fn layers(&self) -> &[ArchiveFormat] {
+        &self.layers
+    }
+
+    /// Format of the inner most layer
+    fn inner_format(&self) -> ArchiveFormat {
+        self.layers[0]
+    }
+
+    /// Write the decompressed content of a single compressed file to `writer`
+    ///
+    /// Containers hold multiple entries and can not be written to a single stream.
+    pub fn unpack_to_writer<W>(self, writer: &mut W) -> Result<()>
+    where
+        W: Write,
+    {
+        let format = self.inner_format();
+
+        match self.content {
+            Content::Stream { mut reader, .. } => {
+                debug!("Unpacking {format} stream to writer");
+
+                io::copy(&mut reader, writer)
+                    .map_err(|source| self.meter.error(Error::unpacker(format, source)))?;
+
+                Ok(())
+            }
+            Content::Tar(_) | Content::Zip(_) => Err(Error::ContainerToStream(format)),
+        }
+    }
+}
+
+impl Unpacker for NestedUnpacker {
+    fn unpack_with(self, path: &Path, options: &UnpackOptions) -> Result<()> {
+        let format = self.inner_format();
+        let meter = self.meter;
+
+        let result = match self.content {
+            Content::Tar(reader) => TarUnpacker::new(reader)?.unpack_with(path, options),
+            Content::Zip(file) => ZipUnpacker::new(file)?.unpack_with(path, options),
+            Content::Stream {
+                mut reader,
+                name,
+                mtime,
+            } => {
+                debug!("Unpacking {format} stream to `{}`", path.display());
+
+                unpack_stream(&mut reader, format, &name, path, mtime, options)
+            }
+        };
+
+        result.map_err(|error| meter.error(error))
+    }
+
+    fn entries_with(self, limits: &Limits) -> Result<Vec<Entry>> {
+        let format = self.inner_format();
+        let meter = self.meter;
+
+        let result = match self.content {
+            Content::Tar(reader) => TarUnpacker::new(reader)?.entries_with(limits),
+            Content::Zip(file) => ZipUnpacker::new(file)?.entries_with(limits),
+            Content::Stream {
+                mut reader,
+                name,
+                mtime,
+            } => stream_entry(&mut reader, format, name, mtime.unwrap_or_default(), limits)
+                .map(|entry| vec![entry]),
+        };
+
+        result.map_err(|error| meter.error(error))
+    }
+}
+
+/// Copy a zip archive into a temporary file to make it seekable
+fn buffer<R>(mut reader: R) -> Result<File>
+where
+    R: Read,
+{
+    let map_err = |source| Error::BufferNestedArchive {
+        format: ArchiveFormat::Zip,
+        source,
+    };
+
+    let mut file = tempfile::tempfile().map_err(map_err)?;
+    io::copy(&mut reader, &mut file).map_err(map_err)?;
+    file.seek(SeekFrom::Start(0)).map_err(map_err)?;
+
+    Ok(file)
+}
+
+/// Name of the content of a compressed file
+///
+/// `file.tar.gz` becomes `file.tar` and `file.tgz` becomes `file.tar`. Names without an
+/// extension of the format get `.out` appended so unpacking never replaces the archive.
+fn decompressed_name(name: &OsStr, format: ArchiveFormat) -> OsString {
+    let name = name.to_string_lossy();
+
+    if let Some((stem, ext)) = name.rsplit_once('.').filter(|(stem, _)| !stem.is_empty()) {
+        match ArchiveFormat::try_from_ext(ext).as_slice() {
+            [codec] if *codec == format => return OsString::from(stem),
+            [ArchiveFormat::Tar, codec] if *codec == format => {
+                return OsString::from(format!("{stem}.tar"))
+            }
+            _ => {}
+        }
+    }
+
+    OsString::from(format!("{name}.out"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        error::Error,
+        fs::{self, read_to_string},
+        io::Write,
+    };
+
+    use flate2::{write::GzEncoder, Compression};
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+    use xz2::write::XzEncoder;
+
+    use super::*;
+
+    /// Create test logger instance
+    fn init() {
+        let _ = env_logger::builder().is_test(true).try_init();
+    }
+
+    /// test unpacking a tar archive in gzip in xz
+    #[test]
+    fn unpack_tar_gz_xz() -> Result<(), Box<dyn Error>> {
+        init();
+        const FILE_NAME: &str = "some-file.txt";
+        const FILE_CONTENT: &str = "some test text";
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+
+        // create test file
+
+        let file_path = working_directory.path().join(FILE_NAME);
+        fs::write(&file_path, FILE_CONTENT)?;
+
+        // create tar archive and compress it twice
+
+        let mut tar = tar::Builder::new(Vec::new());
+        tar.append_path_with_name(&file_path, FILE_NAME)?;
+        let tar = tar.into_inner()?;
+
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(&tar)?;
+
+        let archive_path = working_directory.path().join("archive.tar.gz.xz");
+        let mut xz = XzEncoder::new(File::create(&archive_path)?, 6);
+        xz.write_all(&gzip.finish()?)?;
+        xz.finish()?;
+
+        // run unpacker
+
+        let out_dir = tempdir()?;
+
+        let unpacker = NestedUnpacker::open(&archive_path)?;
+        assert_eq!(
+            unpacker.layers(),
+            [ArchiveFormat::Tar, ArchiveFormat::GZip, ArchiveFormat::Xz]
+        );
+        unpacker.unpack(out_dir.path())?;
+
+        // run test
+
+        let content = read_to_string(out_dir.path().join(FILE_NAME))?;
+        assert_eq!(content, FILE_CONTENT);
+
+        Ok(())
+    }
+
+    /// test the layers are taken from the file name even if the content looks like zip
+    #[test]
+    fn unpack_by_name() -> Result<(), Box<dyn Error>> {
+        init();
+        // a tar header starts with the entry name, this one with the zip magic
+        const FILE_NAME: &str = "PK\u{3}\u{4}-file.txt";
+        const FILE_CONTENT: &str = "some test text";
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+
+        // create tar archive and compress it
+
+        let mut header = tar::Header::new_gnu();
+        header.set_size(FILE_CONTENT.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+
+        let mut tar = tar::Builder::new(Vec::new());
+        tar.append_data(&mut header, FILE_NAME, FILE_CONTENT.as_bytes())?;
+        let tar = tar.into_inner()?;
+        assert_eq!(detect::detect_bytes(&tar), Some(ArchiveFormat::Zip));
+
+        let archive_path = working_directory.path().join("data.tar.zst");
+        fs::write(&archive_path, zstd::encode_all(tar.as_slice(), 0)?)?;
+
+        // run unpacker
+
+        let out_dir = tempdir()?;
+
+        let unpacker = NestedUnpacker::open(&archive_path)?;
+        assert_eq!(unpacker.layers(), [ArchiveFormat::Tar, ArchiveFormat::Zstd]);
+        unpacker.unpack(out_dir.path())?;
+
+        // run test
+
+        let content = read_to_string(out_dir.path().join(FILE_NAME))?;
+        assert_eq!(content, FILE_CONTENT);
+
+        Ok(())
+    }
+
+    /// test a compressed document which is a zip archive is restored instead of unpacked
+    #[test]
+    fn unpack_compressed_document() -> Result<(), Box<dyn Error>> {
+        init();
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+
+        // create a zip based document and compress it
+
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        zip.start_file("word/document.xml", Default::default())?;
+        zip.write_all(b"<document/>")?;
+        let document = zip.finish()?.into_inner();
+
+        let archive_path = working_directory.path().join("report.docx.zst");
+        fs::write(&archive_path, zstd::encode_all(document.as_slice(), 0)?)?;
+
+        // run unpacker
+
+        let out_dir = tempdir()?;
+
+        let unpacker = NestedUnpacker::open(&archive_path)?;
+        assert_eq!(unpacker.layers(), [ArchiveFormat::Zstd]);
+        unpacker.unpack(out_dir.path())?;
+
+        // run test
+
+        assert_eq!(fs::read(out_dir.path().join("report.docx"))?, document);
+
+        Ok(())
+    }
+
+    /// test decompression bombs are stopped at the limits
+    #[test]
+    fn limits() -> Result<(), Box<dyn Error>> {
+        init();
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+
+        // create a gzip file of zeros in tar in gzip
+
+        let zeros = vec![0; 4 << 20];
+
+        let archive_path = working_directory.path().join("zeros.gz");
+        let mut gzip = GzEncoder::new(File::create(&archive_path)?, Compression::best());
+        gzip.write_all(&zeros)?;
+        gzip.finish()?;
+
+        let mut tar = tar::Builder::new(Vec::new());
+        tar.append_path_with_name(&archive_path, "zeros.gz")?;
+        let nested_path = working_directory.path().join("nested.tar.gz");
+        let mut gzip = GzEncoder::new(File::create(&nested_path)?, Compression::best());
+        gzip.write_all(&tar.into_inner()?)?;
+        gzip.finish()?;
+
+        // run tests
+
+        let out_dir = tempdir()?;
+        let cases = [
+            (
+                &archive_path,
+                Limits {
+                    max_total_size: Some(1 << 20),
+                    ..Limits::unlimited()
+                },
+                Limit::TotalSize(1 << 20),
+            ),
+            (
+                &archive_path,
+                Limits {
+                    max_entry_size: Some(1 << 20),
+                    ..Limits::unlimited()
+                },
+                Limit::EntrySize(1 << 20),
+            ),
+            (
+                &archive_path,
+                Limits {
+                    max_ratio: Some(100),
+                    ..Limits::unlimited()
+                },
+                Limit::Ratio(100),
+            ),
+            (
+                &nested_path,
+                Limits {
+                    max_layers: Some(1),
+                    ..Limits::unlimited()
+                },
+                Limit::Layers(1),
+            ),
+            (
+                &nested_path,
+                Limits {
+                    max_entries: Some(0),
+                    ..Limits::unlimited()
+                },
+                Limit::Entries(0),
+            ),
+        ];
+
+        for (path, limits, expected) in cases {
+            let options = UnpackOptions {
+                limits,
+                ..UnpackOptions::default()
+            };
+            let result = NestedUnpacker::open_with(path, &limits)
+                .and_then(|unpacker| unpacker.unpack_with(out_dir.path(), &options));
+
+            assert!(
+                matches!(result, Err(crate::error::Error::LimitExceeded(limit)) if limit == expected),
+                "{expected:?}: {result:?}"
+            );
+        }
+
+        // without limits the archive unpacks
+
+        let options = UnpackOptions {
+            limits: Limits::unlimited(),
+            ..UnpackOptions::default()
+        };
+        NestedUnpacker::open_with(&archive_path, &options.limits)?
+            .unpack_with(out_dir.path(), &options)?;
+        assert_eq!(fs::read(out_dir.path().join("zeros"))?, zeros);
+
+        Ok(())
+    }
+
+    /// test names of decompressed files
+    #[test]
+    fn decompressed_names() {
+        let cases = [
+            ("file.txt.gz", ArchiveFormat::GZip, "file.txt"),
+            ("file.tgz", ArchiveFormat::GZip, "file.tar"),
+            ("file.tar.zst", ArchiveFormat::Zstd, "file.tar"),
+            ("file.gz", ArchiveFormat::Xz, "file.gz.out"),
+            ("download", ArchiveFormat::GZip, "download.out"),
+            (".gz", ArchiveFormat::GZip, ".gz.out"),
+        ];
+
+        for (name, format, expected) in cases {
+            assert_eq!(
+                decompressed_name(OsStr::new(name), format),
+                OsStr::new(expected)
+            );
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    str::FromStr,
+};
+
+use brick::{
+    error::{Error, Result},
+    info::{ArchiveInfo, Entry, Summary},
+};
+use chrono::NaiveDateTime;
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::macros::fl;
+
+/// Format used to print listings
+#[derive(Debug, Clone, Copy)]
+pub enum OutputFormat {
+    /// Human readable listing like `tar -tv`
+    Text,
+    /// A single JSON document containing the format, layers, all entries and the summary
+    Json,
+    /// One JSON object per line for each entry followed by the summary
+    Ndjson,
+    /// A header row followed by one row per entry, then a blank line and the summary table
+    Csv,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        Self::Text
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "ndjson" => Ok(Self::Ndjson),
+            "csv" => Ok(Self::Csv),
+            _ => Err(Error::InvalidOutputFormat(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputFormat::Text => write!(f, "text"),
+            OutputFormat::Json => write!(f, "json"),
+            OutputFormat::Ndjson => write!(f, "ndjson"),
+            OutputFormat::Csv => write!(f, "csv"),
+        }
+    }
+}
+
+impl OutputFormat {
+    pub const fn all() -> &'static [&'static str] {
+        &["text", "json", "ndjson", "csv"]
+    }
+}
+
+/// A single line of NDJSON output
+#[derive(Serialize)]
+#[serde(tag = "record", rename_all = "lowercase")]
+enum Record<'a> {
+    Entry(&'a Entry),
+    Summary(&'a Summary),
+}
+
+/// Write the listing of an archive to `writer`
+pub fn write_info<W>(mut writer: W, info: &ArchiveInfo, format: OutputFormat) -> Result<()>
+where
+    W: Write,
+{
+    match format {
+        OutputFormat::Text => write_text(&mut writer, info).map_err(Error::WriteOutput)?,
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, info)?;
+            writeln!(writer).map_err(Error::WriteOutput)?;
+        }
+        OutputFormat::Ndjson => {
+            for entry in &info.entries {
+                serde_json::to_writer(&mut writer, &Record::Entry(entry))?;
+                writeln!(writer).map_err(Error::WriteOutput)?;
+            }
+
+            serde_json::to_writer(&mut writer, &Record::Summary(&info.summary))?;
+            writeln!(writer).map_err(Error::WriteOutput)?;
+        }
+        OutputFormat::Csv => {
+            let mut csv = csv::Writer::from_writer(&mut writer);
+
+            for entry in &info.entries {
+                csv.serialize(entry)?;
+            }
+
+            csv.flush().map_err(Error::WriteOutput)?;
+            drop(csv);
+
+            // the summary has different columns and follows as a second table
+            writeln!(writer).map_err(Error::WriteOutput)?;
+
+            let mut csv = csv::Writer::from_writer(&mut writer);
+            csv.serialize(&info.summary)?;
+            csv.flush().map_err(Error::WriteOutput)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_text<W>(writer: &mut W, info: &ArchiveInfo) -> io::Result<()>
+where
+    W: Write,
+{
+    for entry in &info.entries {
+        writeln!(writer, "{}", format_entry(entry))?;
+    }
+
+    writeln!(writer)?;
+    writeln!(
+        writer,
+        "{}",
+        fl!("info-format", format = info.format.to_string())
+    )?;
+    writeln!(
+        writer,
+        "{}",
+        fl!("info-layers", layers = info.layers.iter().join(", "))
+    )?;
+    writeln!(
+        writer,
+        "{}",
+        fl!("info-entries", count = info.summary.entries)
+    )?;
+    writeln!(
+        writer,
+        "{}",
+        fl!(
+            "info-uncompressed-size",
+            size = info.summary.uncompressed_size
+        )
+    )?;
+    writeln!(
+        writer,
+        "{}",
+        fl!("info-archive-size", size = info.summary.archive_size)
+    )?;
+    writeln!(
+        writer,
+        "{}",
+        fl!(
+            "info-compression-ratio",
+            ratio = format!("{:.2}", info.summary.compression_ratio)
+        )
+    )?;
+
+    Ok(())
+}
+
+/// Format an entry like a line of `tar -tv`
+fn format_entry(entry: &Entry) -> String {
+    let mut permissions = String::with_capacity(10);
+    permissions.push(entry.entry_type.as_char());
+    for shift in [6, 3, 0] {
+        let bits = entry.mode >> shift;
+        permissions.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        permissions.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        permissions.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+    }
+
+    let user = entry.user.clone().unwrap_or_else(|| entry.uid.to_string());
+    let group = entry.group.clone().unwrap_or_else(|| entry.gid.to_string());
+
+    let mtime = i64::try_from(entry.mtime)
+        .ok()
+        .and_then(|secs| NaiveDateTime::from_timestamp_opt(secs, 0))
+        .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| entry.mtime.to_string());
+
+    let mut line = format!(
+        "{permissions} {:<17} {:>10} {mtime} {}",
+        format!("{user}/{group}"),
+        entry.size,
+        entry.path.display()
+    );
+
+    if let Some(target) = &entry.link_target {
+        line.push_str(" -> ");
+        line.push_str(&target.display().to_string());
+    }
+
+    line
+}
@@ -0,0 +1,266 @@
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use bzip2::{read::MultiBzDecoder, write::BzEncoder, Compression};
+use log::debug;
+
+use crate::{
+    error::{Error, Result},
+    extract::{Limits, UnpackOptions},
+    info::Entry,
+    walk::WalkOptions,
+    ArchiveFormat, CompressionLevel,
+};
+
+use super::{modified_time, stream_entry, stream_name, unpack_stream, Packer, Unpacker};
+
+impl From<CompressionLevel> for Compression {
+    fn from(level: CompressionLevel) -> Self {
+        match level {
+            CompressionLevel::Auto => Compression::default(),
+            CompressionLevel::Numbered(n) => Compression::new(n.clamp(1, 9) as u32),
+        }
+    }
+}
+
+pub struct Bzip2Packer<W>
+where
+    W: Write,
+{
+    writer: W,
+    level: Compression,
+}
+
+impl<W> Bzip2Packer<W>
+where
+    W: Write,
+{
+    pub fn new(file: W, level: CompressionLevel) -> Result<Self> {
+        Ok(Self {
+            writer: file,
+            level: level.into(),
+        })
+    }
+}
+
+impl<W> Packer for Bzip2Packer<W>
+where
+    W: Write,
+{
+    fn add_dir(&mut self, path: &Path, _options: &WalkOptions) -> Result<()> {
+        Err(Error::DirectoryNotSupported {
+            format: ArchiveFormat::Bzip2,
+            path: path.display().to_string(),
+        })
+    }
+
+    /// Compress a file as a new bzip2 stream
+    ///
+    /// Adding multiple files results in concatenated bzip2 streams.
+    fn add_file(&mut self, path: &Path) -> Result<()> {
+        debug!("Adding file `{}` to archive", path.display());
+
+        let mut file = File::open(path).map_err(|source| Error::OpenInputFile {
+            source,
+            path: path.display().to_string(),
+        })?;
+
+        let mut encoder = BzEncoder::new(&mut self.writer, self.level);
+        io::copy(&mut file, &mut encoder).map_err(Error::Bzip2Packer)?;
+        encoder.finish().map_err(Error::Bzip2Packer)?;
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        debug!("Finishing bzip2 packer");
+
+        self.writer.flush().map_err(Error::Bzip2Packer)?;
+
+        Ok(())
+    }
+}
+
+pub struct Bzip2Unpacker<R>
+where
+    R: Read,
+{
+    decoder: MultiBzDecoder<R>,
+    /// Name of the decompressed file, bzip2 does not store one
+    name: OsString,
+    mtime: Option<u64>,
+}
+
+impl<R> Bzip2Unpacker<R>
+where
+    R: Read,
+{
+    pub fn new(reader: R) -> Result<Self> {
+        Ok(Self {
+            decoder: MultiBzDecoder::new(reader),
+            name: OsString::from("archive"),
+            mtime: None,
+        })
+    }
+}
+
+impl Bzip2Unpacker<File> {
+    pub fn open(path: &Path) -> Result<Self> {
+        debug!("Opening bzip2 archive `{}`", path.display());
+
+        let file = File::open(path).map_err(|source| Error::OpenArchiveFile {
+            source,
+            path: path.display().to_string(),
+        })?;
+        let mtime = modified_time(&file);
+
+        let mut unpacker = Self::new(file)?;
+        unpacker.name = stream_name(path);
+        unpacker.mtime = mtime;
+
+        Ok(unpacker)
+    }
+}
+
+/// Reading the unpacker yields the decompressed data
+impl<R> Read for Bzip2Unpacker<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.decoder.read(buf)
+    }
+}
+
+impl<R> Unpacker for Bzip2Unpacker<R>
+where
+    R: Read,
+{
+    fn unpack_with(mut self, path: &Path, options: &UnpackOptions) -> Result<()> {
+        debug!("Unpacking bzip2 archive to `{}`", path.display());
+
+        unpack_stream(
+            &mut self.decoder,
+            ArchiveFormat::Bzip2,
+            &self.name,
+            path,
+            self.mtime,
+            options,
+        )?;
+
+        Ok(())
+    }
+
+    fn entries_with(mut self, limits: &Limits) -> Result<Vec<Entry>> {
+        debug!("Listing bzip2 archive entries");
+
+        let entry = stream_entry(
+            &mut self.decoder,
+            ArchiveFormat::Bzip2,
+            self.name,
+            self.mtime.unwrap_or_default(),
+            limits,
+        )?;
+
+        Ok(vec![entry])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        error::Error,
+        fs::{self, read_to_string},
+    };
+
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    /// Create test logger instance
+    fn init() {
+        let _ = env_logger::builder().is_test(true).try_init();
+    }
+
+    /// test packer and unpacker with a single file
+    #[test]
+    fn pack_and_unpack_file() -> Result<(), Box<dyn Error>> {
+        init();
+        const FILE_CONTENT: &[u8] = b"some test text";
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+
+        // create test file
+
+        let file_path = working_directory.path().join("some-file.txt");
+        fs::write(&file_path, FILE_CONTENT)?;
+
+        // run packer
+
+        let archive_path = working_directory.path().join("some-file.txt.bz2");
+        let mut packer =
+            Bzip2Packer::new(File::create(&archive_path)?, CompressionLevel::Numbered(9))?;
+        packer.add_path(&file_path)?;
+        packer.finish()?;
+
+        // run test: the stream starts with the bzip2 magic and the block size
+
+        let archive = fs::read(&archive_path)?;
+        assert_eq!(&archive[..4], b"BZh9");
+
+        // run unpacker
+
+        let out_dir = tempdir()?;
+
+        let unpacker = Bzip2Unpacker::open(&archive_path)?;
+        unpacker.unpack(out_dir.path())?;
+
+        // run test: the name is derived from the archive name
+
+        let content = read_to_string(out_dir.path().join("some-file.txt"))?;
+        assert_eq!(content.as_bytes(), FILE_CONTENT);
+
+        Ok(())
+    }
+
+    /// test unpacker with multiple streams like pbzip2 writes them
+    #[test]
+    fn unpack_multi_stream() -> Result<(), Box<dyn Error>> {
+        init();
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+
+        // create archive with two streams
+
+        let archive_path = working_directory.path().join("some-file.txt.bz2");
+        let mut archive = File::create(&archive_path)?;
+        for part in [b"some ", b"text."] {
+            let mut encoder = BzEncoder::new(&mut archive, Compression::fast());
+            encoder.write_all(part)?;
+            encoder.finish()?;
+        }
+
+        // run unpacker
+
+        let out_dir = tempdir()?;
+
+        let unpacker = Bzip2Unpacker::open(&archive_path)?;
+        unpacker.unpack(out_dir.path())?;
+
+        // run test
+
+        let content = read_to_string(out_dir.path().join("some-file.txt"))?;
+        assert_eq!(content, "some text.");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,318 @@
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+use flate2::{read::MultiGzDecoder, Compression, GzBuilder};
+use log::debug;
+
+use crate::{
+    error::{Error, Result},
+    extract::{Limits, UnpackOptions},
+    info::Entry,
+    reproducible::Reproducible,
+    walk::WalkOptions,
+    ArchiveFormat, CompressionLevel,
+};
+
+use super::{stream_entry, stream_name, unpack_stream, Packer, Unpacker};
+
+impl From<CompressionLevel> for Compression {
+    fn from(level: CompressionLevel) -> Self {
+        match level {
+            CompressionLevel::Auto => Compression::default(),
+            CompressionLevel::Numbered(n) => Compression::new(n.clamp(0, 9) as u32),
+        }
+    }
+}
+
+pub struct GZipPacker<W>
+where
+    W: Write,
+{
+    writer: W,
+    level: Compression,
+    reproducible: Option<Reproducible>,
+}
+
+impl<W> GZipPacker<W>
+where
+    W: Write,
+{
+    pub fn new(file: W, level: CompressionLevel) -> Result<Self> {
+        Ok(Self {
+            writer: file,
+            level: level.into(),
+            reproducible: None,
+        })
+    }
+
+    /// Leave the modification time out of the headers of all files added afterwards
+    ///
+    /// The header then stores 0 which gzip reads as no time being available.
+    pub fn reproducible(mut self, reproducible: Option<Reproducible>) -> Self {
+        self.reproducible = reproducible;
+        self
+    }
+}
+
+impl<W> Packer for GZipPacker<W>
+where
+    W: Write,
+{
+    fn add_dir(&mut self, path: &Path, _options: &WalkOptions) -> Result<()> {
+        Err(Error::DirectoryNotSupported {
+            format: ArchiveFormat::GZip,
+            path: path.display().to_string(),
+        })
+    }
+
+    /// Compress a file as a new gzip member
+    ///
+    /// Adding multiple files results in a multi-member gzip stream.
+    fn add_file(&mut self, path: &Path) -> Result<()> {
+        debug!("Adding file `{}` to archive", path.display());
+
+        let mut file = File::open(path).map_err(|source| Error::OpenInputFile {
+            source,
+            path: path.display().to_string(),
+        })?;
+
+        let mtime = match self.reproducible {
+            Some(_) => 0,
+            None => file
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs().try_into().unwrap_or_default())
+                .unwrap_or_default(),
+        };
+
+        let mut builder = GzBuilder::new().mtime(mtime);
+        if let Some(name) = path.file_name() {
+            builder = builder.filename(name.to_string_lossy().as_bytes());
+        }
+
+        let mut encoder = builder.write(&mut self.writer, self.level);
+        io::copy(&mut file, &mut encoder).map_err(Error::GZipPacker)?;
+        encoder.finish().map_err(Error::GZipPacker)?;
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        debug!("Finishing gzip packer");
+
+        self.writer.flush().map_err(Error::GZipPacker)?;
+
+        Ok(())
+    }
+}
+
+pub struct GZipUnpacker<R>
+where
+    R: Read,
+{
+    decoder: MultiGzDecoder<R>,
+    /// File name used when the gzip header does not contain one
+    fallback_name: Option<OsString>,
+}
+
+impl<R> GZipUnpacker<R>
+where
+    R: Read,
+{
+    pub fn new(reader: R) -> Result<Self> {
+        Ok(Self {
+            decoder: MultiGzDecoder::new(reader),
+            fallback_name: None,
+        })
+    }
+
+    /// File name stored in the FNAME header field
+    ///
+    /// Only the last component is used so a crafted header can not point outside of the
+    /// destination.
+    pub fn header_name(&self) -> Option<OsString> {
+        self.decoder
+            .header()
+            .and_then(|header| header.filename())
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .and_then(|name| Path::new(&name).file_name().map(ToOwned::to_owned))
+    }
+
+    /// Modification time stored in the MTIME header field
+    pub fn header_mtime(&self) -> Option<u32> {
+        self.decoder
+            .header()
+            .map(|header| header.mtime())
+            .filter(|mtime| *mtime != 0)
+    }
+}
+
+impl GZipUnpacker<File> {
+    pub fn open(path: &Path) -> Result<Self> {
+        debug!("Opening gzip archive `{}`", path.display());
+
+        let file = File::open(path).map_err(|source| Error::OpenArchiveFile {
+            source,
+            path: path.display().to_string(),
+        })?;
+
+        let mut unpacker = Self::new(file)?;
+        unpacker.fallback_name = Some(stream_name(path));
+
+        Ok(unpacker)
+    }
+}
+
+/// Reading the unpacker yields the decompressed data
+impl<R> Read for GZipUnpacker<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.decoder.read(buf)
+    }
+}
+
+impl<R> Unpacker for GZipUnpacker<R>
+where
+    R: Read,
+{
+    fn unpack_with(mut self, path: &Path, options: &UnpackOptions) -> Result<()> {
+        debug!("Unpacking gzip archive to `{}`", path.display());
+
+        let name = self
+            .header_name()
+            .or_else(|| self.fallback_name.clone())
+            .unwrap_or_else(|| OsString::from("archive"));
+        let mtime = self.header_mtime().map(Into::into);
+
+        unpack_stream(
+            &mut self.decoder,
+            ArchiveFormat::GZip,
+            &name,
+            path,
+            mtime,
+            options,
+        )?;
+
+        Ok(())
+    }
+
+    fn entries_with(mut self, limits: &Limits) -> Result<Vec<Entry>> {
+        debug!("Listing gzip archive entries");
+
+        let name = self
+            .header_name()
+            .or_else(|| self.fallback_name.clone())
+            .unwrap_or_default();
+        let mtime = self.header_mtime().unwrap_or_default();
+
+        let entry = stream_entry(
+            &mut self.decoder,
+            ArchiveFormat::GZip,
+            name,
+            mtime.into(),
+            limits,
+        )?;
+
+        Ok(vec![entry])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        error::Error,
+        fs::{self, read_to_string},
+    };
+
+    use flate2::write::GzEncoder;
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    /// Create test logger instance
+    fn init() {
+        let _ = env_logger::builder().is_test(true).try_init();
+    }
+
+    /// test packer and unpacker with a single file
+    #[test]
+    fn pack_and_unpack_file() -> Result<(), Box<dyn Error>> {
+        init();
+        const FILE_NAME: &str = "some-file.txt";
+        const FILE_CONTENT: &[u8] = b"some test text";
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+
+        // create test file
+
+        let file_path = working_directory.path().join(FILE_NAME);
+        fs::write(&file_path, FILE_CONTENT)?;
+
+        // run packer
+
+        let archive_path = working_directory.path().join("archive.gz");
+        let mut packer = GZipPacker::new(File::create(&archive_path)?, CompressionLevel::Auto)?;
+        packer.add_path(&file_path)?;
+        packer.finish()?;
+
+        // run unpacker
+
+        let out_dir = tempdir()?;
+
+        let unpacker = GZipUnpacker::open(&archive_path)?;
+        unpacker.unpack(out_dir.path())?;
+
+        // run test: the name is taken from the FNAME header field
+
+        let content = read_to_string(out_dir.path().join(FILE_NAME))?;
+        assert_eq!(content.as_bytes(), FILE_CONTENT);
+
+        Ok(())
+    }
+
+    /// test unpacker with a stream of multiple members
+    #[test]
+    fn unpack_multi_member() -> Result<(), Box<dyn Error>> {
+        init();
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+
+        // create archive with two members
+
+        let archive_path = working_directory.path().join("some-file.txt.gz");
+        let mut archive = File::create(&archive_path)?;
+        for part in [b"some ", b"text."] {
+            let mut encoder = GzEncoder::new(&mut archive, Compression::default());
+            encoder.write_all(part)?;
+            encoder.finish()?;
+        }
+
+        // run unpacker
+
+        let out_dir = tempdir()?;
+
+        let unpacker = GZipUnpacker::open(&archive_path)?;
+        unpacker.unpack(out_dir.path())?;
+
+        // run test: without FNAME the name is derived from the archive name
+
+        let content = read_to_string(out_dir.path().join("some-file.txt"))?;
+        assert_eq!(content, "some text.");
+
+        Ok(())
+    }
+}
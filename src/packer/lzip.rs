@@ -0,0 +1,613 @@
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::Path,
+};
+
+use flate2::Crc;
+use log::debug;
+use xz2::{
+    stream::{Action, LzmaOptions, Status, Stream},
+    write::XzEncoder,
+};
+
+use crate::{
+    error::{Error, Result},
+    extract::{Limits, UnpackOptions},
+    info::Entry,
+    walk::WalkOptions,
+    ArchiveFormat, CompressionLevel,
+};
+
+use super::{
+    modified_time, stream_entry, stream_name, unpack_stream, xz::preset, Packer, Unpacker,
+};
+
+/// Magic bytes at the start of every lzip member
+const MAGIC: &[u8; 4] = b"LZIP";
+
+const VERSION: u8 = 1;
+
+/// Size of the magic, version and dictionary size
+const HEADER_SIZE: u64 = 6;
+
+/// Size of the CRC32, data size and member size
+const TRAILER_SIZE: u64 = 20;
+
+/// Size of the header liblzma writes and expects for `.lzma` streams
+const LZMA_HEADER_SIZE: usize = 13;
+
+/// Literal context, literal position and position bits required by lzip (lc=3, lp=0, pb=2)
+const LZMA_PROPERTIES: u8 = 0x5D;
+
+const MIN_DICT_SIZE_LOG: u32 = 12;
+const MAX_DICT_SIZE_LOG: u32 = 29;
+
+/// Base 2 logarithm of the dictionary size of the liblzma presets 0 to 9
+const PRESET_DICT_SIZE_LOGS: [u32; 10] = [18, 20, 21, 22, 22, 23, 23, 24, 25, 26];
+
+/// Writes a single lzip member
+///
+/// lzip uses the raw LZMA stream of the `.lzma` format with fixed properties. The encoder
+/// drops the `.lzma` header and frames the stream with the lzip header and trailer.
+pub struct LzipEncoder<W>
+where
+    W: Write,
+{
+    encoder: XzEncoder<MemberWriter<W>>,
+    crc: Crc,
+    data_size: u64,
+}
+
+impl<W> LzipEncoder<W>
+where
+    W: Write,
+{
+    pub fn new(mut writer: W, preset: u32) -> io::Result<Self> {
+        let mut options = LzmaOptions::new_preset(preset)?;
+
+        let dict_size_log = PRESET_DICT_SIZE_LOGS[preset.min(9) as usize];
+
+        options
+            .dict_size(1 << dict_size_log)
+            .literal_context_bits(3)
+            .literal_position_bits(0)
+            .position_bits(2);
+        let stream = Stream::new_lzma_encoder(&options)?;
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION, dict_size_log as u8])?;
+
+        Ok(Self {
+            encoder: XzEncoder::new_stream(
+                MemberWriter {
+                    writer,
+                    skip: LZMA_HEADER_SIZE,
+                    written: 0,
+                },
+                stream,
+            ),
+            crc: Crc::new(),
+            data_size: 0,
+        })
+    }
+
+    /// Finish the LZMA stream, write the trailer and return the underlying writer
+    pub fn finish(self) -> io::Result<W> {
+        let MemberWriter {
+            mut writer,
+            written,
+            ..
+        } = self.encoder.finish()?;
+
+        writer.write_all(&self.crc.sum().to_le_bytes())?;
+        writer.write_all(&self.data_size.to_le_bytes())?;
+        writer.write_all(&(HEADER_SIZE + written + TRAILER_SIZE).to_le_bytes())?;
+
+        Ok(writer)
+    }
+}
+
+impl<W> Write for LzipEncoder<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.encoder.write(buf)?;
+        self.crc.update(&buf[..n]);
+        self.data_size += n as u64;
+
+        Ok(n)
+    }
+
+    /// Flush the underlying writer
+    ///
+    /// The `.lzma` encoder does not support sync points, compressed data is only complete
+    /// after [LzipEncoder::finish].
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder.get_mut().flush()
+    }
+}
+
+/// Strips the `.lzma` header and counts the size of the compressed data
+struct MemberWriter<W> {
+    writer: W,
+    skip: usize,
+    written: u64,
+}
+
+impl<W> Write for MemberWriter<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.skip > 0 {
+            let skipped = self.skip.min(buf.len());
+            self.skip -= skipped;
+            return Ok(skipped);
+        }
+
+        let n = self.writer.write(buf)?;
+        self.written += n as u64;
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Reads all members of an lzip file
+pub struct LzipDecoder<R>
+where
+    R: BufRead,
+{
+    reader: R,
+    member: Option<Member>,
+    /// Number of members read so far
+    members: u64,
+}
+
+/// State of the member currently being decoded
+struct Member {
+    stream: Stream,
+    /// Synthetic `.lzma` header not yet passed to liblzma
+    header: Vec<u8>,
+    crc: Crc,
+    data_size: u64,
+    member_size: u64,
+}
+
+impl<R> LzipDecoder<R>
+where
+    R: BufRead,
+{
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            member: None,
+            members: 0,
+        }
+    }
+
+    /// Read the header of the next member
+    ///
+    /// Returns `false` at the end of the file. Data following the last member which does
+    /// not start with the lzip magic is ignored like `lzip` does.
+    fn next_member(&mut self) -> io::Result<bool> {
+        if self.members > 0 && self.reader.fill_buf()?.is_empty() {
+            return Ok(false);
+        }
+
+        let mut header = [0; HEADER_SIZE as usize];
+        match self.reader.read_exact(&mut header) {
+            Err(error) if self.members > 0 && error.kind() == io::ErrorKind::UnexpectedEof => {
+                return Ok(false)
+            }
+            result => result?,
+        }
+
+        if &header[..4] != MAGIC {
+            if self.members > 0 {
+                return Ok(false);
+            }
+
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not an lzip file",
+            ));
+        }
+
+        if header[4] != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported lzip version {}", header[4]),
+            ));
+        }
+
+        let dict_size = dict_size(header[5])?;
+
+        let mut lzma_header = Vec::with_capacity(LZMA_HEADER_SIZE);
+        lzma_header.push(LZMA_PROPERTIES);
+        lzma_header.extend_from_slice(&dict_size.to_le_bytes());
+        lzma_header.extend_from_slice(&u64::MAX.to_le_bytes());
+
+        self.member = Some(Member {
+            stream: Stream::new_lzma_decoder(u64::MAX)?,
+            header: lzma_header,
+            crc: Crc::new(),
+            data_size: 0,
+            member_size: HEADER_SIZE,
+        });
+        self.members += 1;
+
+        Ok(true)
+    }
+
+    /// Read and verify the trailer of the current member
+    fn finish_member(&mut self, member: Member) -> io::Result<()> {
+        let mut trailer = [0; TRAILER_SIZE as usize];
+        self.reader.read_exact(&mut trailer)?;
+
+        let [crc, data_size, member_size] = [&trailer[..4], &trailer[4..12], &trailer[12..]];
+
+        if crc != member.crc.sum().to_le_bytes() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "lzip member CRC mismatch",
+            ));
+        }
+
+        if data_size != member.data_size.to_le_bytes()
+            || member_size != (member.member_size + TRAILER_SIZE).to_le_bytes()
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "lzip member size mismatch",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl<R> Read for LzipDecoder<R>
+where
+    R: BufRead,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.member.is_none() && !self.next_member()? {
+                return Ok(0);
+            }
+
+            let member = self.member.as_mut().expect("member was read");
+
+            let from_header = !member.header.is_empty();
+            let input = if from_header {
+                &member.header[..]
+            } else {
+                self.reader.fill_buf()?
+            };
+            let eof = input.is_empty();
+
+            let before_in = member.stream.total_in();
+            let before_out = member.stream.total_out();
+            let status = member.stream.process(input, buf, Action::Run)?;
+            let consumed = (member.stream.total_in() - before_in) as usize;
+            let read = (member.stream.total_out() - before_out) as usize;
+
+            if from_header {
+                member.header.drain(..consumed);
+            } else {
+                self.reader.consume(consumed);
+                member.member_size += consumed as u64;
+            }
+
+            member.crc.update(&buf[..read]);
+            member.data_size += read as u64;
+
+            if status == Status::StreamEnd {
+                let member = self.member.take().expect("member was read");
+                self.finish_member(member)?;
+            } else if eof && read == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "truncated lzip member",
+                ));
+            } else if consumed == 0 && read == 0 && !buf.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "corrupt lzip member",
+                ));
+            }
+
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+        }
+    }
+}
+
+/// Decode the coded dictionary size of the lzip header
+///
+/// Bits 4-0 hold the base 2 logarithm of the base size, bits 7-5 the number of sixteenths
+/// to subtract from it.
+fn dict_size(coded: u8) -> io::Result<u32> {
+    let log = u32::from(coded & 0x1F);
+    if !(MIN_DICT_SIZE_LOG..=MAX_DICT_SIZE_LOG).contains(&log) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid lzip dictionary size",
+        ));
+    }
+
+    let base = 1u32 << log;
+    Ok(base - (base / 16) * u32::from(coded >> 5))
+}
+
+pub struct LzipPacker<W>
+where
+    W: Write,
+{
+    writer: W,
+    preset: u32,
+}
+
+impl<W> LzipPacker<W>
+where
+    W: Write,
+{
+    pub fn new(file: W, level: CompressionLevel) -> Result<Self> {
+        Ok(Self {
+            writer: file,
+            preset: preset(level),
+        })
+    }
+}
+
+impl<W> Packer for LzipPacker<W>
+where
+    W: Write,
+{
+    fn add_dir(&mut self, path: &Path, _options: &WalkOptions) -> Result<()> {
+        Err(Error::DirectoryNotSupported {
+            format: ArchiveFormat::Lzip,
+            path: path.display().to_string(),
+        })
+    }
+
+    /// Compress a file as a new lzip member
+    ///
+    /// Adding multiple files results in a multi-member lzip file.
+    fn add_file(&mut self, path: &Path) -> Result<()> {
+        debug!("Adding file `{}` to archive", path.display());
+
+        let mut file = File::open(path).map_err(|source| Error::OpenInputFile {
+            source,
+            path: path.display().to_string(),
+        })?;
+
+        let mut encoder =
+            LzipEncoder::new(&mut self.writer, self.preset).map_err(Error::LzipPacker)?;
+        io::copy(&mut file, &mut encoder).map_err(Error::LzipPacker)?;
+        encoder.finish().map_err(Error::LzipPacker)?;
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        debug!("Finishing lzip packer");
+
+        self.writer.flush().map_err(Error::LzipPacker)?;
+
+        Ok(())
+    }
+}
+
+pub struct LzipUnpacker<R>
+where
+    R: BufRead,
+{
+    decoder: LzipDecoder<R>,
+    /// Name of the decompressed file, lzip does not store one
+    name: OsString,
+    mtime: Option<u64>,
+}
+
+impl<R> LzipUnpacker<R>
+where
+    R: BufRead,
+{
+    pub fn new(reader: R) -> Result<Self> {
+        Ok(Self {
+            decoder: LzipDecoder::new(reader),
+            name: OsString::from("archive"),
+            mtime: None,
+        })
+    }
+}
+
+impl LzipUnpacker<BufReader<File>> {
+    pub fn open(path: &Path) -> Result<Self> {
+        debug!("Opening lzip archive `{}`", path.display());
+
+        let file = File::open(path).map_err(|source| Error::OpenArchiveFile {
+            source,
+            path: path.display().to_string(),
+        })?;
+        let mtime = modified_time(&file);
+
+        let mut unpacker = Self::new(BufReader::new(file))?;
+        unpacker.name = stream_name(path);
+        unpacker.mtime = mtime;
+
+        Ok(unpacker)
+    }
+}
+
+/// Reading the unpacker yields the decompressed data
+impl<R> Read for LzipUnpacker<R>
+where
+    R: BufRead,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.decoder.read(buf)
+    }
+}
+
+impl<R> Unpacker for LzipUnpacker<R>
+where
+    R: BufRead,
+{
+    fn unpack_with(mut self, path: &Path, options: &UnpackOptions) -> Result<()> {
+        debug!("Unpacking lzip archive to `{}`", path.display());
+
+        unpack_stream(
+            &mut self.decoder,
+            ArchiveFormat::Lzip,
+            &self.name,
+            path,
+            self.mtime,
+            options,
+        )?;
+
+        Ok(())
+    }
+
+    fn entries_with(mut self, limits: &Limits) -> Result<Vec<Entry>> {
+        debug!("Listing lzip archive entries");
+
+        let entry = stream_entry(
+            &mut self.decoder,
+            ArchiveFormat::Lzip,
+            self.name,
+            self.mtime.unwrap_or_default(),
+            limits,
+        )?;
+
+        Ok(vec![entry])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        error::Error,
+        fs::{self, read_to_string},
+    };
+
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    /// Create test logger instance
+    fn init() {
+        let _ = env_logger::builder().is_test(true).try_init();
+    }
+
+    /// test packer and unpacker with a single file
+    #[test]
+    fn pack_and_unpack_file() -> Result<(), Box<dyn Error>> {
+        init();
+        const FILE_CONTENT: &[u8] = b"some test text";
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+
+        // create test file
+
+        let file_path = working_directory.path().join("some-file.txt");
+        fs::write(&file_path, FILE_CONTENT)?;
+
+        // run packer
+
+        let archive_path = working_directory.path().join("some-file.txt.lz");
+        let mut packer = LzipPacker::new(File::create(&archive_path)?, CompressionLevel::Auto)?;
+        packer.add_path(&file_path)?;
+        packer.finish()?;
+
+        // run test: the member starts with the lzip header and ends with the member size
+
+        let archive = fs::read(&archive_path)?;
+        assert_eq!(&archive[..5], b"LZIP\x01");
+        assert_eq!(
+            &archive[archive.len() - 8..],
+            (archive.len() as u64).to_le_bytes()
+        );
+
+        // run unpacker
+
+        let out_dir = tempdir()?;
+
+        let unpacker = LzipUnpacker::open(&archive_path)?;
+        unpacker.unpack(out_dir.path())?;
+
+        // run test: the name is derived from the archive name
+
+        let content = read_to_string(out_dir.path().join("some-file.txt"))?;
+        assert_eq!(content.as_bytes(), FILE_CONTENT);
+
+        Ok(())
+    }
+
+    /// test decoder with multiple members followed by trailing data
+    #[test]
+    fn decode_multi_member() -> Result<(), Box<dyn Error>> {
+        init();
+
+        // create archive with two members
+
+        let mut archive = Vec::new();
+        for part in [&b"some "[..], &b"text."[..]] {
+            let mut encoder = LzipEncoder::new(&mut archive, 0)?;
+            encoder.write_all(part)?;
+            encoder.finish()?;
+        }
+        archive.extend_from_slice(b"trailing garbage");
+
+        // run decoder
+
+        let mut content = String::new();
+        LzipDecoder::new(&archive[..]).read_to_string(&mut content)?;
+
+        // run test
+
+        assert_eq!(content, "some text.");
+
+        Ok(())
+    }
+
+    /// test decoder rejects a member with a wrong checksum
+    #[test]
+    fn decode_corrupt_crc() -> Result<(), Box<dyn Error>> {
+        init();
+
+        // create archive and flip a bit of the CRC
+
+        let mut archive = Vec::new();
+        let mut encoder = LzipEncoder::new(&mut archive, 0)?;
+        encoder.write_all(b"some text")?;
+        encoder.finish()?;
+
+        let crc_offset = archive.len() - TRAILER_SIZE as usize;
+        archive[crc_offset] ^= 1;
+
+        // run decoder
+
+        let result = LzipDecoder::new(&archive[..]).read_to_end(&mut Vec::new());
+
+        // run test
+
+        assert_eq!(
+            result.map_err(|error| error.kind()),
+            Err(io::ErrorKind::InvalidData)
+        );
+
+        Ok(())
+    }
+}
@@ -1,34 +1,103 @@
+pub mod bzip2;
+pub mod gzip;
+pub mod lzip;
 pub mod tar;
+pub mod xz;
+pub mod zip;
+pub mod zstd;
 
-use std::path::Path;
+use std::{
+    ffi::{OsStr, OsString},
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    time::UNIX_EPOCH,
+};
 
-use walkdir::WalkDir;
+use ::bzip2::write::BzEncoder;
+use filetime::FileTime;
+use flate2::write::GzEncoder;
+use log::warn;
+use xz2::write::XzEncoder;
 
-use crate::error::Result;
+use crate::{
+    error::{Error, Result},
+    extract::{copy_stream, Destination, Limits, UnpackOptions},
+    info::{Entry, EntryType},
+    walk::{walk, FileKind, WalkOptions},
+    ArchiveFormat, CompressionLevel,
+};
+
+use self::{
+    bzip2::Bzip2Unpacker,
+    gzip::GZipUnpacker,
+    lzip::{LzipEncoder, LzipUnpacker},
+    xz::{LzmaUnpacker, XzUnpacker},
+    zstd::ZstdUnpacker,
+};
 
 pub trait Packer {
+    /// Add a path to the packer walking directories with the default options
+    fn add_path(&mut self, path: &Path) -> Result<()> {
+        self.add_path_with(path, &WalkOptions::default())
+    }
+
     /// Add a path to the packer
     ///
     /// If the path points to a file add the file (see [Packer::add_file])
     /// otherwise add a directory (see [Packer::add_dir])
-    fn add_path(&mut self, path: &Path) -> Result<()> {
+    fn add_path_with(&mut self, path: &Path, options: &WalkOptions) -> Result<()> {
         if path.is_dir() {
-            self.add_dir(path)
+            self.add_dir(path, options)
         } else {
             self.add_file(path)
         }
     }
 
     /// Recursively add a directory and all files within to the packer
-    fn add_dir(&mut self, path: &Path) -> Result<()> {
-        for entry in WalkDir::new(path) {
+    ///
+    /// The directory is traversed by [walk] and each entry is added according to its kind.
+    fn add_dir(&mut self, path: &Path, options: &WalkOptions) -> Result<()> {
+        for entry in walk(path, options) {
             let entry = entry?;
-            self.add_path(entry.path())?;
+
+            match entry.kind {
+                FileKind::Directory => self.add_directory(&entry.path)?,
+                FileKind::File => self.add_file(&entry.path)?,
+                FileKind::Symlink => self.add_symlink(&entry.path)?,
+                FileKind::Special => self.add_special(&entry.path)?,
+            }
         }
 
         Ok(())
     }
 
+    /// Add a single directory without the files within
+    ///
+    /// Formats without directory entries only store the files within.
+    fn add_directory(&mut self, _path: &Path) -> Result<()> {
+        Ok(())
+    }
+
+    /// Add a symlink as a link
+    ///
+    /// Formats without links store the content of the target instead.
+    fn add_symlink(&mut self, path: &Path) -> Result<()> {
+        self.add_file(path)
+    }
+
+    /// Add a fifo, socket or device file
+    ///
+    /// Formats without special files skip it.
+    fn add_special(&mut self, path: &Path) -> Result<()> {
+        warn!(
+            "Skipping special file `{}`, the format can not store it",
+            path.display()
+        );
+
+        Ok(())
+    }
+
     /// Add a file to the packer
     fn add_file(&mut self, path: &Path) -> Result<()>;
 
@@ -36,10 +105,278 @@ pub trait Packer {
     fn finish(self) -> Result<()>;
 }
 
+/// Unpacks an archive read from any reader
+///
+/// Unpackers of files are created with an inherent `open` function.
 pub trait Unpacker: Sized {
-    /// Open an archive
-    fn open(path: &Path) -> Result<Self>;
+    /// Unpack the archive to the path with the default options
+    fn unpack(self, path: &Path) -> Result<()> {
+        self.unpack_with(path, &UnpackOptions::default())
+    }
 
     /// Unpack the archive to the path
-    fn unpack(self, path: &Path) -> Result<()>;
+    fn unpack_with(self, path: &Path, options: &UnpackOptions) -> Result<()>;
+
+    /// List the metadata of all entries in the archive with the default limits
+    fn entries(self) -> Result<Vec<Entry>> {
+        self.entries_with(&Limits::default())
+    }
+
+    /// List the metadata of all entries in the archive
+    fn entries_with(self, limits: &Limits) -> Result<Vec<Entry>>;
+}
+
+/// Name of the file in a single file archive derived from the archive path
+///
+/// `file.txt.gz` is named `file.txt`.
+pub(crate) fn stream_name(path: &Path) -> OsString {
+    path.file_stem()
+        .map(ToOwned::to_owned)
+        .unwrap_or_else(|| OsString::from("archive"))
+}
+
+/// Modification time of a file in seconds since the unix epoch
+pub(crate) fn modified_time(file: &File) -> Option<u64> {
+    file.metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+}
+
+/// Decompress a single file stream of `format` to a file called `name` in the directory `path`
+///
+/// The modification time `mtime` is applied to the file if the stream stores one.
+pub(crate) fn unpack_stream<R>(
+    reader: &mut R,
+    format: ArchiveFormat,
+    name: &OsStr,
+    path: &Path,
+    mtime: Option<u64>,
+    options: &UnpackOptions,
+) -> Result<()>
+where
+    R: Read,
+{
+    let map_err = |source| Error::unpacker(format, source);
+
+    let destination = Destination::create(path, format, options)?;
+
+    let output_path = match destination.entry_path(Path::new(name))? {
+        Some(output_path) => output_path,
+        None => return Ok(()),
+    };
+    let output_path = match destination.prepare(&output_path, false, mtime)? {
+        Some(output_path) => output_path,
+        None => return Ok(()),
+    };
+
+    let mut output = File::create(&output_path).map_err(map_err)?;
+    copy_stream(reader, &mut output, format, &options.limits)?;
+
+    if let Some(mtime) = mtime {
+        filetime::set_file_mtime(&output_path, FileTime::from_unix_time(mtime as i64, 0))
+            .map_err(map_err)?;
+    }
+
+    Ok(())
+}
+
+/// Describe a single file stream of `format` by decompressing it
+pub(crate) fn stream_entry<R>(
+    reader: &mut R,
+    format: ArchiveFormat,
+    name: OsString,
+    mtime: u64,
+    limits: &Limits,
+) -> Result<Entry>
+where
+    R: Read,
+{
+    let size = copy_stream(reader, &mut io::sink(), format, limits)?;
+
+    Ok(Entry {
+        path: name.into(),
+        entry_type: EntryType::File,
+        size,
+        mode: 0,
+        uid: 0,
+        gid: 0,
+        user: None,
+        group: None,
+        mtime,
+        link_target: None,
+    })
+}
+
+/// Metadata stored in the header of a single file compression stream
+#[derive(Debug, Default)]
+pub(crate) struct StreamHeader {
+    pub name: Option<OsString>,
+    pub mtime: Option<u64>,
+}
+
+/// Wrap `reader` in the decoder of the compression `format`
+///
+/// # Panics
+///
+/// Panics if `format` is a container format.
+pub(crate) fn decoder<'a, R>(
+    format: ArchiveFormat,
+    reader: R,
+) -> Result<(Box<dyn Read + 'a>, StreamHeader)>
+where
+    R: Read + 'a,
+{
+    let decoder: Box<dyn Read + 'a> = match format {
+        ArchiveFormat::GZip => {
+            let unpacker = GZipUnpacker::new(reader)?;
+            let header = StreamHeader {
+                name: unpacker.header_name(),
+                mtime: unpacker.header_mtime().map(Into::into),
+            };
+
+            return Ok((Box::new(unpacker), header));
+        }
+        ArchiveFormat::Xz => Box::new(XzUnpacker::new(reader)?),
+        ArchiveFormat::LzmaAlone => Box::new(LzmaUnpacker::new(reader)?),
+        ArchiveFormat::Lzip => Box::new(LzipUnpacker::new(BufReader::new(reader))?),
+        ArchiveFormat::Zstd => Box::new(ZstdUnpacker::new(reader)?),
+        ArchiveFormat::Bzip2 => Box::new(Bzip2Unpacker::new(reader)?),
+        ArchiveFormat::Tar | ArchiveFormat::Zip => {
+            unreachable!("{format} is a container and has no decoder")
+        }
+    };
+
+    Ok((decoder, StreamHeader::default()))
+}
+
+/// Writer compressing all data written to it
+///
+/// Encoders are chained from the inner most to the outer most format, finishing an encoder
+/// also finishes all encoders it writes to.
+pub trait Encoder: Write {
+    /// Finish the compressed stream and all streams below it
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+/// The archive file is the end of every chain of encoders
+impl<W> Encoder for BufWriter<W>
+where
+    W: Write,
+{
+    fn finish(self: Box<Self>) -> Result<()> {
+        self.into_inner()
+            .map_err(|error| Error::WriteArchiveFile(error.into_error()))?
+            .flush()
+            .map_err(Error::WriteArchiveFile)
+    }
+}
+
+/// A single compression layer in a chain of encoders
+struct Layer<'a, E> {
+    format: ArchiveFormat,
+    encoder: E,
+    /// Finish the compressed stream and return the next encoder
+    finish: fn(E) -> io::Result<Box<dyn Encoder + 'a>>,
+}
+
+impl<'a, E> Write for Layer<'a, E>
+where
+    E: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encoder.write(buf)
+    }
+
+    /// Flushing does nothing
+    ///
+    /// Not every format supports sync points and flushing in between would only hurt the
+    /// compression ratio, the data is complete after [Encoder::finish].
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a, E> Encoder for Layer<'a, E>
+where
+    E: Write,
+{
+    fn finish(self: Box<Self>) -> Result<()> {
+        let Layer {
+            format,
+            encoder,
+            finish,
+        } = *self;
+
+        finish(encoder)
+            .map_err(|source| Error::packer(format, source))?
+            .finish()
+    }
+}
+
+/// Wrap `writer` in the encoder of the compression `format`
+///
+/// Container formats can not be streamed into another format and have to be packed first.
+pub fn encoder<'a>(
+    writer: Box<dyn Encoder + 'a>,
+    format: ArchiveFormat,
+    level: CompressionLevel,
+    long_window_log: Option<u32>,
+) -> Result<Box<dyn Encoder + 'a>> {
+    fn layer<'a, E>(
+        format: ArchiveFormat,
+        encoder: E,
+        finish: fn(E) -> io::Result<Box<dyn Encoder + 'a>>,
+    ) -> Box<dyn Encoder + 'a>
+    where
+        E: Write + 'a,
+    {
+        Box::new(Layer {
+            format,
+            encoder,
+            finish,
+        })
+    }
+
+    let map_err = |source| Error::packer(format, source);
+
+    let encoder = match format {
+        ArchiveFormat::GZip => layer(
+            format,
+            GzEncoder::new(writer, level.into()),
+            GzEncoder::finish,
+        ),
+        ArchiveFormat::Xz => layer(
+            format,
+            XzEncoder::new(writer, xz::preset(level)),
+            XzEncoder::finish,
+        ),
+        ArchiveFormat::LzmaAlone => layer(
+            format,
+            xz::lzma_encoder(writer, xz::preset(level)).map_err(map_err)?,
+            XzEncoder::finish,
+        ),
+        ArchiveFormat::Lzip => layer(
+            format,
+            LzipEncoder::new(writer, xz::preset(level)).map_err(map_err)?,
+            LzipEncoder::finish,
+        ),
+        ArchiveFormat::Zstd => layer(
+            format,
+            zstd::frame_encoder(writer, zstd::compression_level(level), long_window_log)
+                .map_err(map_err)?,
+            ::zstd::stream::write::Encoder::finish,
+        ),
+        ArchiveFormat::Bzip2 => layer(
+            format,
+            BzEncoder::new(writer, level.into()),
+            BzEncoder::finish,
+        ),
+        ArchiveFormat::Tar | ArchiveFormat::Zip => {
+            return Err(Error::ContainerNotInnermost(format))
+        }
+    };
+
+    Ok(encoder)
 }
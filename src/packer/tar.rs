@@ -1,17 +1,31 @@
-use std::{io::Write, path::Path};
+use std::{
+    borrow::Cow,
+    fs::{self, File, Metadata},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
 
 use log::debug;
-use tar::Builder;
+use tar::{Archive, Builder, Header, HeaderMode};
 
-use crate::error::{Error, Result};
+use crate::{
+    error::{Error, Result},
+    extract::{Budget, Destination, Limits, UnpackOptions},
+    info::{Entry, EntryType},
+    rename::Rename,
+    reproducible::Reproducible,
+    ArchiveFormat,
+};
 
-use super::Packer;
+use super::{Packer, Unpacker};
 
 pub struct TarPacker<W>
 where
     W: Write,
 {
     tar: Builder<W>,
+    rename: Rename,
+    reproducible: Option<Reproducible>,
 }
 
 impl<W> TarPacker<W>
@@ -21,20 +35,124 @@ where
     pub fn new(file: W) -> Result<Self> {
         Ok(Self {
             tar: Builder::new(file),
+            rename: Rename::default(),
+            reproducible: None,
         })
     }
+
+    /// Rewrite the names of all entries added afterwards
+    pub fn rename(mut self, rename: Rename) -> Self {
+        self.rename = rename;
+        self
+    }
+
+    /// Normalize the metadata of all entries added afterwards
+    ///
+    /// Files, directories and symlinks get the owner, permissions and clamped time of
+    /// `reproducible`, special files the fixed time of tar's deterministic headers.
+    pub fn reproducible(mut self, reproducible: Option<Reproducible>) -> Self {
+        let mode = match reproducible {
+            Some(_) => HeaderMode::Deterministic,
+            None => HeaderMode::Complete,
+        };
+
+        self.tar.mode(mode);
+        self.reproducible = reproducible;
+        self
+    }
+
+    /// Name of the entry of the file at `path`, `None` skips the file
+    fn entry_name(&self, path: &Path) -> Option<PathBuf> {
+        let name = self.rename.archive_name(path);
+
+        if name.is_none() {
+            debug!("Skipping `{}` without a name left", path.display());
+        }
+
+        name
+    }
+
+    /// Append the entry `name` for the file at `path` with normalized metadata
+    fn append_reproducible(
+        &mut self,
+        path: &Path,
+        name: &Path,
+        metadata: &Metadata,
+        reproducible: &Reproducible,
+    ) -> io::Result<()> {
+        // uid and gid are 0 and the empty user and group names of new headers are kept
+        let mut header = Header::new_gnu();
+        header.set_metadata_in_mode(metadata, HeaderMode::Deterministic);
+        header.set_mtime(reproducible.mtime(metadata));
+        header.set_mode(reproducible.mode(metadata));
+
+        if metadata.file_type().is_symlink() {
+            self.tar
+                .append_link(&mut header, name, fs::read_link(path)?)
+        } else if metadata.is_dir() {
+            self.tar.append_data(&mut header, name, io::empty())
+        } else {
+            self.tar.append_data(&mut header, name, File::open(path)?)
+        }
+    }
 }
 
 impl<W> Packer for TarPacker<W>
 where
     W: Write,
 {
-    fn add_dir(&mut self, path: &Path) -> Result<()> {
+    fn add_directory(&mut self, path: &Path) -> Result<()> {
         debug!("Adding directory `{}` to archive", path.display());
 
-        self.tar
-            .append_dir_all(path, path)
-            .map_err(Error::TarPacker)?;
+        let name = match self.entry_name(path) {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+
+        // directory names end with a slash like GNU tar writes them
+        let name = name.join("");
+
+        match self.reproducible {
+            Some(reproducible) => fs::metadata(path).and_then(|metadata| {
+                self.append_reproducible(path, &name, &metadata, &reproducible)
+            }),
+            None => self.tar.append_dir(name, path),
+        }
+        .map_err(Error::TarPacker)
+    }
+
+    fn add_symlink(&mut self, path: &Path) -> Result<()> {
+        debug!("Adding symlink `{}` to archive", path.display());
+
+        let name = match self.entry_name(path) {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+
+        if let Some(reproducible) = self.reproducible {
+            return fs::symlink_metadata(path)
+                .and_then(|metadata| {
+                    self.append_reproducible(path, &name, &metadata, &reproducible)
+                })
+                .map_err(Error::TarPacker);
+        }
+
+        // only links found while walking are stored as links, files always store their content
+        self.tar.follow_symlinks(false);
+        let result = self.tar.append_path_with_name(path, name);
+        self.tar.follow_symlinks(true);
+
+        result.map_err(Error::TarPacker)
+    }
+
+    fn add_special(&mut self, path: &Path) -> Result<()> {
+        debug!("Adding special file `{}` to archive", path.display());
+
+        if let Some(name) = self.entry_name(path) {
+            self.tar
+                .append_path_with_name(path, name)
+                .map_err(Error::TarPacker)?;
+        }
 
         Ok(())
     }
@@ -42,9 +160,18 @@ where
     fn add_file(&mut self, path: &Path) -> Result<()> {
         debug!("Adding file `{}` to archive", path.display());
 
-        self.tar.append_path(path).map_err(Error::TarPacker)?;
-
-        Ok(())
+        let name = match self.entry_name(path) {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+
+        match self.reproducible {
+            Some(reproducible) => fs::metadata(path).and_then(|metadata| {
+                self.append_reproducible(path, &name, &metadata, &reproducible)
+            }),
+            None => self.tar.append_path_with_name(path, name),
+        }
+        .map_err(Error::TarPacker)
     }
 
     fn finish(self) -> Result<()> {
@@ -56,19 +183,174 @@ where
     }
 }
 
+pub struct TarUnpacker<R>
+where
+    R: Read,
+{
+    tar: Archive<R>,
+}
+
+impl<R> TarUnpacker<R>
+where
+    R: Read,
+{
+    pub fn new(reader: R) -> Result<Self> {
+        Ok(Self {
+            tar: Archive::new(reader),
+        })
+    }
+}
+
+impl TarUnpacker<File> {
+    pub fn open(path: &Path) -> Result<Self> {
+        debug!("Opening tar archive `{}`", path.display());
+
+        let file = File::open(path).map_err(|source| Error::OpenArchiveFile {
+            source,
+            path: path.display().to_string(),
+        })?;
+
+        Self::new(file)
+    }
+}
+
+impl<R> Unpacker for TarUnpacker<R>
+where
+    R: Read,
+{
+    fn unpack_with(mut self, path: &Path, options: &UnpackOptions) -> Result<()> {
+        debug!("Unpacking tar archive to `{}`", path.display());
+
+        let destination = Destination::create(path, ArchiveFormat::Tar, options)?;
+        self.tar
+            .set_preserve_permissions(options.preserve_permissions);
+        let mut budget = Budget::new(&options.limits);
+
+        // metadata of directories is applied last so adding files does not change it
+        let mut directories = Vec::new();
+
+        for entry in self.tar.entries().map_err(Error::TarUnpacker)? {
+            let mut entry = entry.map_err(Error::TarUnpacker)?;
+            let name = entry.path().map_err(Error::TarUnpacker)?.into_owned();
+            let entry_type = entry.header().entry_type();
+
+            // the size in the header is exactly what tar reads for the entry
+            budget.add_entry(entry.size())?;
+
+            let output_path = match destination.entry_path(&name)? {
+                Some(output_path) => output_path,
+                None => continue,
+            };
+            let link_target = entry
+                .link_name()
+                .map_err(Error::TarUnpacker)?
+                .map(Cow::into_owned);
+
+            // links are checked before an existing file is replaced by them
+            let link_source = match link_target {
+                Some(target) if entry_type.is_symlink() => {
+                    destination.check_symlink(&output_path, &target)?;
+                    None
+                }
+                // hard links point to an earlier entry and not relative to the link
+                Some(target) if entry_type.is_hard_link() => {
+                    Some(destination.link_source(&output_path, &target)?)
+                }
+                _ => None,
+            };
+
+            let output_path = match destination.prepare(
+                &output_path,
+                entry_type.is_dir(),
+                entry.header().mtime().ok(),
+            )? {
+                Some(output_path) => output_path,
+                None => continue,
+            };
+
+            if entry_type.is_dir() {
+                directories.push((entry, output_path));
+            } else if let Some(source) = link_source {
+                fs::hard_link(&source, &output_path).map_err(Error::TarUnpacker)?;
+            } else {
+                entry.unpack(&output_path).map_err(Error::TarUnpacker)?;
+            }
+        }
+
+        for (mut entry, output_path) in directories {
+            entry.unpack(&output_path).map_err(Error::TarUnpacker)?;
+        }
+
+        Ok(())
+    }
+
+    fn entries_with(mut self, limits: &Limits) -> Result<Vec<Entry>> {
+        debug!("Listing tar archive entries");
+
+        let mut budget = Budget::new(limits);
+
+        self.tar
+            .entries()
+            .map_err(Error::TarUnpacker)?
+            .map(|entry| {
+                let entry = entry.map_err(Error::TarUnpacker)?;
+                let header = entry.header();
+
+                budget.add_entry(entry.size())?;
+
+                let entry_type = match header.entry_type() {
+                    tar::EntryType::Regular | tar::EntryType::Continuous => EntryType::File,
+                    tar::EntryType::Directory => EntryType::Directory,
+                    tar::EntryType::Symlink => EntryType::Symlink,
+                    tar::EntryType::Link => EntryType::HardLink,
+                    _ => EntryType::Other,
+                };
+
+                Ok(Entry {
+                    path: entry.path().map_err(Error::TarUnpacker)?.into_owned(),
+                    entry_type,
+                    size: entry.size(),
+                    mode: header.mode().map_err(Error::TarUnpacker)?,
+                    uid: header.uid().map_err(Error::TarUnpacker)?,
+                    gid: header.gid().map_err(Error::TarUnpacker)?,
+                    user: header
+                        .username()
+                        .ok()
+                        .flatten()
+                        .filter(|name| !name.is_empty())
+                        .map(ToOwned::to_owned),
+                    group: header
+                        .groupname()
+                        .ok()
+                        .flatten()
+                        .filter(|name| !name.is_empty())
+                        .map(ToOwned::to_owned),
+                    mtime: header.mtime().map_err(Error::TarUnpacker)?,
+                    link_target: entry
+                        .link_name()
+                        .map_err(Error::TarUnpacker)?
+                        .map(|target| target.into_owned()),
+                })
+            })
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::{
         env,
         error::Error,
         fs::{self, read_to_string, File},
+        io,
     };
 
     use pretty_assertions::assert_eq;
-    use tar::Archive;
+    use tar::{Archive, Header};
     use tempfile::{tempdir, NamedTempFile};
 
     use super::*;
+    use crate::error;
 
     /// Create test logger instance
     fn init() {
@@ -181,4 +463,185 @@ mod tests {
 
         Ok(())
     }
+
+    /// test unpacker with an archive created by tar
+    #[test]
+    fn unpack_directory() -> Result<(), Box<dyn Error>> {
+        init();
+        const DIRECTORY_NAME: &str = "directory";
+        const INNER_FILE_NAME: &str = "inner-file.txt";
+        const INNER_FILE_CONTENT: &[u8] = b"some test text";
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+
+        // create directory
+
+        let directory = working_directory.path().join(DIRECTORY_NAME);
+        fs::create_dir(&directory)?;
+
+        // create inner test file
+
+        let inner_file_path = directory.join(INNER_FILE_NAME);
+        let mut inner_file = File::create(&inner_file_path)?;
+        inner_file.write_all(INNER_FILE_CONTENT)?;
+
+        // create archive with tar
+
+        let archive_path = working_directory.path().join("archive.tar");
+        let mut tar = Builder::new(File::create(&archive_path)?);
+        tar.append_dir_all(DIRECTORY_NAME, &directory)?;
+        tar.into_inner()?;
+
+        // run unpacker
+
+        let out_dir = tempdir()?;
+
+        let unpacker = TarUnpacker::open(&archive_path)?;
+        unpacker.unpack(out_dir.path())?;
+
+        // run test
+
+        let content = read_to_string(out_dir.path().join(DIRECTORY_NAME).join(INNER_FILE_NAME))?;
+        assert_eq!(content.as_bytes(), INNER_FILE_CONTENT);
+
+        Ok(())
+    }
+
+    /// Write an archive of entries with raw names which tar refuses to write itself
+    fn malicious_archive(
+        path: &Path,
+        entries: &[(&str, tar::EntryType, &str)],
+    ) -> Result<(), Box<dyn Error>> {
+        const CONTENT: &[u8] = b"malicious";
+
+        let mut tar = Builder::new(File::create(path)?);
+
+        for (name, entry_type, link_name) in entries {
+            let mut header = Header::new_gnu();
+            let gnu = header.as_gnu_mut().unwrap();
+            gnu.name[..name.len()].copy_from_slice(name.as_bytes());
+            gnu.linkname[..link_name.len()].copy_from_slice(link_name.as_bytes());
+            header.set_entry_type(*entry_type);
+            header.set_mode(0o644);
+            header.set_size(if entry_type.is_file() {
+                CONTENT.len() as u64
+            } else {
+                0
+            });
+            header.set_cksum();
+
+            if entry_type.is_file() {
+                tar.append(&header, CONTENT)?;
+            } else {
+                tar.append(&header, io::empty())?;
+            }
+        }
+
+        tar.into_inner()?;
+
+        Ok(())
+    }
+
+    /// test unpacker refuses entries leading outside of the destination
+    #[test]
+    fn refuse_unsafe_paths() -> Result<(), Box<dyn Error>> {
+        init();
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+        let outside = working_directory.path().join("outside");
+        fs::create_dir(&outside)?;
+        let out_dir = working_directory.path().join("out");
+        fs::create_dir(&out_dir)?;
+
+        // an earlier unpack left a symlink leading outside
+
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&outside, out_dir.join("escape"))?;
+
+        // refused links must not replace existing files
+
+        fs::write(out_dir.join("symlink"), "existing")?;
+        fs::write(out_dir.join("hardlink"), "existing")?;
+
+        let absolute = outside.join("absolute.txt");
+        let cases = [
+            vec![("../outside/parent.txt", tar::EntryType::Regular, "")],
+            vec![(absolute.to_str().unwrap(), tar::EntryType::Regular, "")],
+            vec![("symlink", tar::EntryType::Symlink, "../outside")],
+            vec![(
+                "symlink",
+                tar::EntryType::Symlink,
+                outside.to_str().unwrap(),
+            )],
+            vec![("hardlink", tar::EntryType::Link, "../outside/file.txt")],
+            #[cfg(unix)]
+            vec![("escape/through.txt", tar::EntryType::Regular, "")],
+            // a later link leaves the destination through an earlier one
+            #[cfg(unix)]
+            vec![
+                ("current", tar::EntryType::Symlink, "."),
+                ("chained", tar::EntryType::Symlink, "current/../outside"),
+            ],
+        ];
+
+        for entries in cases {
+            let archive_path = working_directory.path().join("archive.tar");
+            malicious_archive(&archive_path, &entries)?;
+
+            let result = TarUnpacker::open(&archive_path)?.unpack(&out_dir);
+
+            assert!(
+                matches!(
+                    result,
+                    Err(error::Error::UnsafeEntryPath(_) | error::Error::UnsafeLinkTarget { .. })
+                ),
+                "{entries:?}: {result:?}"
+            );
+        }
+
+        // run tests
+
+        assert_eq!(fs::read_dir(&outside)?.count(), 0);
+        assert_eq!(read_to_string(out_dir.join("symlink"))?, "existing");
+        assert_eq!(read_to_string(out_dir.join("hardlink"))?, "existing");
+        assert!(fs::symlink_metadata(out_dir.join("chained")).is_err());
+
+        Ok(())
+    }
+
+    /// test unpacker writes outside of the destination if asked to
+    #[test]
+    fn unsafe_paths() -> Result<(), Box<dyn Error>> {
+        init();
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+        let out_dir = working_directory.path().join("out");
+
+        let archive_path = working_directory.path().join("archive.tar");
+        malicious_archive(
+            &archive_path,
+            &[("../parent.txt", tar::EntryType::Regular, "")],
+        )?;
+
+        // run unpacker
+
+        let options = UnpackOptions {
+            unsafe_paths: true,
+            ..UnpackOptions::default()
+        };
+        TarUnpacker::open(&archive_path)?.unpack_with(&out_dir, &options)?;
+
+        // run test
+
+        let content = read_to_string(working_directory.path().join("parent.txt"))?;
+        assert_eq!(content, "malicious");
+
+        Ok(())
+    }
 }
@@ -0,0 +1,465 @@
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use log::debug;
+use xz2::{
+    read::XzDecoder,
+    stream::{LzmaOptions, Stream},
+    write::XzEncoder,
+};
+
+use crate::{
+    error::{Error, Result},
+    extract::{Limits, UnpackOptions},
+    info::Entry,
+    walk::WalkOptions,
+    ArchiveFormat, CompressionLevel,
+};
+
+use super::{modified_time, stream_entry, stream_name, unpack_stream, Packer, Unpacker};
+
+/// Preset used by `xz` when no level is given
+const DEFAULT_PRESET: u32 = 6;
+
+/// Memory limit of the decoders, the encoder picked the dictionary size already
+const MEMORY_LIMIT: u64 = u64::MAX;
+
+/// Map a compression level to a liblzma preset
+pub(crate) fn preset(level: CompressionLevel) -> u32 {
+    match level {
+        CompressionLevel::Auto => DEFAULT_PRESET,
+        CompressionLevel::Numbered(n) => n.clamp(0, 9) as u32,
+    }
+}
+
+/// Create an encoder writing a `.lzma` stream
+pub(crate) fn lzma_encoder<W>(writer: W, preset: u32) -> io::Result<XzEncoder<W>>
+where
+    W: Write,
+{
+    let stream =
+        LzmaOptions::new_preset(preset).and_then(|options| Stream::new_lzma_encoder(&options))?;
+
+    Ok(XzEncoder::new_stream(writer, stream))
+}
+
+pub struct XzPacker<W>
+where
+    W: Write,
+{
+    writer: W,
+    preset: u32,
+}
+
+impl<W> XzPacker<W>
+where
+    W: Write,
+{
+    pub fn new(file: W, level: CompressionLevel) -> Result<Self> {
+        Ok(Self {
+            writer: file,
+            preset: preset(level),
+        })
+    }
+}
+
+impl<W> Packer for XzPacker<W>
+where
+    W: Write,
+{
+    fn add_dir(&mut self, path: &Path, _options: &WalkOptions) -> Result<()> {
+        Err(Error::DirectoryNotSupported {
+            format: ArchiveFormat::Xz,
+            path: path.display().to_string(),
+        })
+    }
+
+    /// Compress a file as a new xz stream
+    ///
+    /// Adding multiple files results in concatenated xz streams.
+    fn add_file(&mut self, path: &Path) -> Result<()> {
+        debug!("Adding file `{}` to archive", path.display());
+
+        let mut file = File::open(path).map_err(|source| Error::OpenInputFile {
+            source,
+            path: path.display().to_string(),
+        })?;
+
+        let mut encoder = XzEncoder::new(&mut self.writer, self.preset);
+        io::copy(&mut file, &mut encoder).map_err(Error::XzPacker)?;
+        encoder.finish().map_err(Error::XzPacker)?;
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        debug!("Finishing xz packer");
+
+        self.writer.flush().map_err(Error::XzPacker)?;
+
+        Ok(())
+    }
+}
+
+pub struct XzUnpacker<R>
+where
+    R: Read,
+{
+    decoder: XzDecoder<R>,
+    /// Name of the decompressed file, xz does not store one
+    name: OsString,
+    mtime: Option<u64>,
+}
+
+impl<R> XzUnpacker<R>
+where
+    R: Read,
+{
+    pub fn new(reader: R) -> Result<Self> {
+        Ok(Self {
+            decoder: XzDecoder::new_multi_decoder(reader),
+            name: OsString::from("archive"),
+            mtime: None,
+        })
+    }
+}
+
+impl XzUnpacker<File> {
+    pub fn open(path: &Path) -> Result<Self> {
+        debug!("Opening xz archive `{}`", path.display());
+
+        let file = File::open(path).map_err(|source| Error::OpenArchiveFile {
+            source,
+            path: path.display().to_string(),
+        })?;
+        let mtime = modified_time(&file);
+
+        let mut unpacker = Self::new(file)?;
+        unpacker.name = stream_name(path);
+        unpacker.mtime = mtime;
+
+        Ok(unpacker)
+    }
+}
+
+/// Reading the unpacker yields the decompressed data
+impl<R> Read for XzUnpacker<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.decoder.read(buf)
+    }
+}
+
+impl<R> Unpacker for XzUnpacker<R>
+where
+    R: Read,
+{
+    fn unpack_with(mut self, path: &Path, options: &UnpackOptions) -> Result<()> {
+        debug!("Unpacking xz archive to `{}`", path.display());
+
+        unpack_stream(
+            &mut self.decoder,
+            ArchiveFormat::Xz,
+            &self.name,
+            path,
+            self.mtime,
+            options,
+        )?;
+
+        Ok(())
+    }
+
+    fn entries_with(mut self, limits: &Limits) -> Result<Vec<Entry>> {
+        debug!("Listing xz archive entries");
+
+        let entry = stream_entry(
+            &mut self.decoder,
+            ArchiveFormat::Xz,
+            self.name,
+            self.mtime.unwrap_or_default(),
+            limits,
+        )?;
+
+        Ok(vec![entry])
+    }
+}
+
+pub struct LzmaPacker<W>
+where
+    W: Write,
+{
+    writer: Option<W>,
+    preset: u32,
+}
+
+impl<W> LzmaPacker<W>
+where
+    W: Write,
+{
+    pub fn new(file: W, level: CompressionLevel) -> Result<Self> {
+        Ok(Self {
+            writer: Some(file),
+            preset: preset(level),
+        })
+    }
+}
+
+impl<W> Packer for LzmaPacker<W>
+where
+    W: Write,
+{
+    fn add_dir(&mut self, path: &Path, _options: &WalkOptions) -> Result<()> {
+        Err(Error::DirectoryNotSupported {
+            format: ArchiveFormat::LzmaAlone,
+            path: path.display().to_string(),
+        })
+    }
+
+    /// Compress a file as the single `.lzma` stream
+    ///
+    /// The format has no notion of members so only one file can be added.
+    fn add_file(&mut self, path: &Path) -> Result<()> {
+        debug!("Adding file `{}` to archive", path.display());
+
+        let writer = self
+            .writer
+            .as_mut()
+            .ok_or_else(|| Error::MultipleFilesNotSupported {
+                format: ArchiveFormat::LzmaAlone,
+                path: path.display().to_string(),
+            })?;
+
+        let mut file = File::open(path).map_err(|source| Error::OpenInputFile {
+            source,
+            path: path.display().to_string(),
+        })?;
+
+        let mut encoder = lzma_encoder(writer, self.preset).map_err(Error::LzmaPacker)?;
+        io::copy(&mut file, &mut encoder).map_err(Error::LzmaPacker)?;
+        encoder
+            .finish()
+            .map_err(Error::LzmaPacker)?
+            .flush()
+            .map_err(Error::LzmaPacker)?;
+
+        self.writer = None;
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        debug!("Finishing lzma packer");
+
+        if let Some(mut writer) = self.writer {
+            writer.flush().map_err(Error::LzmaPacker)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct LzmaUnpacker<R>
+where
+    R: Read,
+{
+    decoder: XzDecoder<R>,
+    /// Name of the decompressed file, `.lzma` does not store one
+    name: OsString,
+    mtime: Option<u64>,
+}
+
+impl<R> LzmaUnpacker<R>
+where
+    R: Read,
+{
+    pub fn new(reader: R) -> Result<Self> {
+        let stream = Stream::new_lzma_decoder(MEMORY_LIMIT)
+            .map_err(|error| Error::LzmaUnpacker(error.into()))?;
+
+        Ok(Self {
+            decoder: XzDecoder::new_stream(reader, stream),
+            name: OsString::from("archive"),
+            mtime: None,
+        })
+    }
+}
+
+impl LzmaUnpacker<File> {
+    pub fn open(path: &Path) -> Result<Self> {
+        debug!("Opening lzma archive `{}`", path.display());
+
+        let file = File::open(path).map_err(|source| Error::OpenArchiveFile {
+            source,
+            path: path.display().to_string(),
+        })?;
+        let mtime = modified_time(&file);
+
+        let mut unpacker = Self::new(file)?;
+        unpacker.name = stream_name(path);
+        unpacker.mtime = mtime;
+
+        Ok(unpacker)
+    }
+}
+
+/// Reading the unpacker yields the decompressed data
+impl<R> Read for LzmaUnpacker<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.decoder.read(buf)
+    }
+}
+
+impl<R> Unpacker for LzmaUnpacker<R>
+where
+    R: Read,
+{
+    fn unpack_with(mut self, path: &Path, options: &UnpackOptions) -> Result<()> {
+        debug!("Unpacking lzma archive to `{}`", path.display());
+
+        unpack_stream(
+            &mut self.decoder,
+            ArchiveFormat::LzmaAlone,
+            &self.name,
+            path,
+            self.mtime,
+            options,
+        )?;
+
+        Ok(())
+    }
+
+    fn entries_with(mut self, limits: &Limits) -> Result<Vec<Entry>> {
+        debug!("Listing lzma archive entries");
+
+        let entry = stream_entry(
+            &mut self.decoder,
+            ArchiveFormat::LzmaAlone,
+            self.name,
+            self.mtime.unwrap_or_default(),
+            limits,
+        )?;
+
+        Ok(vec![entry])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        error::Error,
+        fs::{self, read_to_string},
+    };
+
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    /// Create test logger instance
+    fn init() {
+        let _ = env_logger::builder().is_test(true).try_init();
+    }
+
+    /// test xz packer and unpacker with a single file
+    #[test]
+    fn pack_and_unpack_xz() -> Result<(), Box<dyn Error>> {
+        init();
+        const FILE_CONTENT: &[u8] = b"some test text";
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+
+        // create test file
+
+        let file_path = working_directory.path().join("some-file.txt");
+        fs::write(&file_path, FILE_CONTENT)?;
+
+        // run packer
+
+        let archive_path = working_directory.path().join("some-file.txt.xz");
+        let mut packer = XzPacker::new(File::create(&archive_path)?, CompressionLevel::Auto)?;
+        packer.add_path(&file_path)?;
+        packer.finish()?;
+
+        // run test: the stream starts with the xz magic
+
+        let archive = fs::read(&archive_path)?;
+        assert_eq!(&archive[..6], b"\xFD7zXZ\x00");
+
+        // run unpacker
+
+        let out_dir = tempdir()?;
+
+        let unpacker = XzUnpacker::open(&archive_path)?;
+        unpacker.unpack(out_dir.path())?;
+
+        // run test: the name is derived from the archive name
+
+        let content = read_to_string(out_dir.path().join("some-file.txt"))?;
+        assert_eq!(content.as_bytes(), FILE_CONTENT);
+
+        Ok(())
+    }
+
+    /// test lzma packer and unpacker with a single file
+    #[test]
+    fn pack_and_unpack_lzma() -> Result<(), Box<dyn Error>> {
+        init();
+        const FILE_CONTENT: &[u8] = b"some test text";
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+
+        // create test file
+
+        let file_path = working_directory.path().join("some-file.txt");
+        fs::write(&file_path, FILE_CONTENT)?;
+
+        // run packer
+
+        let archive_path = working_directory.path().join("some-file.txt.lzma");
+        let mut packer =
+            LzmaPacker::new(File::create(&archive_path)?, CompressionLevel::Numbered(1))?;
+        packer.add_path(&file_path)?;
+
+        // run test: a second file can not be added
+
+        assert!(matches!(
+            packer.add_path(&file_path),
+            Err(crate::error::Error::MultipleFilesNotSupported { .. })
+        ));
+
+        packer.finish()?;
+
+        // run test: the header holds the lc/lp/pb properties and an unknown size
+
+        let archive = fs::read(&archive_path)?;
+        assert_eq!(archive[0], 0x5D);
+        assert_eq!(&archive[5..13], &[0xFF; 8]);
+
+        // run unpacker
+
+        let out_dir = tempdir()?;
+
+        let unpacker = LzmaUnpacker::open(&archive_path)?;
+        unpacker.unpack(out_dir.path())?;
+
+        // run test
+
+        let content = read_to_string(out_dir.path().join("some-file.txt"))?;
+        assert_eq!(content.as_bytes(), FILE_CONTENT);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,749 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read, Seek, Write},
+    path::{Component, Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use filetime::FileTime;
+use log::debug;
+use zip::{
+    result::ZipError, write::FileOptions, CompressionMethod, DateTime, ZipArchive, ZipWriter,
+};
+
+use crate::{
+    error::{Error, Result},
+    extract::{check_ratio, Budget, Destination, Limits, UnpackOptions},
+    info::{Entry, EntryType},
+    rename::Rename,
+    reproducible::Reproducible,
+    ArchiveFormat, CompressionLevel,
+};
+
+use super::{Packer, Unpacker};
+
+/// File type bits of a unix mode
+const S_IFMT: u32 = 0o170000;
+/// File type bits of a symlink
+const S_IFLNK: u32 = 0o120000;
+
+/// Files at least this large need a Zip64 extra field
+const ZIP64_SIZE_THRESHOLD: u64 = u32::MAX as u64;
+
+pub struct ZipPacker<W>
+where
+    W: Write + Seek,
+{
+    zip: ZipWriter<W>,
+    options: FileOptions,
+    rename: Rename,
+    reproducible: Option<Reproducible>,
+}
+
+impl<W> ZipPacker<W>
+where
+    W: Write + Seek,
+{
+    pub fn new(file: W, level: CompressionLevel) -> Result<Self> {
+        let options = match level {
+            CompressionLevel::Auto => FileOptions::default()
+                .compression_method(CompressionMethod::Deflated)
+                .compression_level(None),
+            CompressionLevel::Numbered(0) => {
+                FileOptions::default().compression_method(CompressionMethod::Stored)
+            }
+            CompressionLevel::Numbered(n) => FileOptions::default()
+                .compression_method(CompressionMethod::Deflated)
+                .compression_level(Some(n.clamp(1, 9))),
+        };
+
+        Ok(Self {
+            zip: ZipWriter::new(file),
+            options,
+            rename: Rename::default(),
+            reproducible: None,
+        })
+    }
+
+    /// Rewrite the names of all entries added afterwards
+    pub fn rename(mut self, rename: Rename) -> Self {
+        self.rename = rename;
+        self
+    }
+
+    /// Normalize the times and permissions of all entries added afterwards
+    ///
+    /// Unix permissions are stored on every platform so the archive does not depend on it.
+    pub fn reproducible(mut self, reproducible: Option<Reproducible>) -> Self {
+        self.reproducible = reproducible;
+        self
+    }
+
+    /// Name of the entry of the file at `path`, `None` skips the file
+    fn entry_name(&self, path: &Path) -> Option<String> {
+        let name = self.rename.archive_name(path);
+
+        if name.is_none() {
+            debug!("Skipping `{}` without a name left", path.display());
+        }
+
+        name.map(|name| entry_name(&name))
+    }
+
+    /// Options for an entry with the metadata of `metadata`
+    fn entry_options(&self, metadata: &fs::Metadata) -> FileOptions {
+        if let Some(reproducible) = &self.reproducible {
+            let mtime = UNIX_EPOCH + Duration::from_secs(reproducible.mtime(metadata));
+
+            return self
+                .options
+                .last_modified_time(zip_time(Some(mtime)))
+                .large_file(metadata.len() >= ZIP64_SIZE_THRESHOLD)
+                .unix_permissions(reproducible.mode(metadata));
+        }
+
+        let mut options = self
+            .options
+            .last_modified_time(zip_time(metadata.modified().ok()))
+            .large_file(metadata.len() >= ZIP64_SIZE_THRESHOLD);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            options = options.unix_permissions(metadata.permissions().mode());
+        }
+
+        options
+    }
+}
+
+impl<W> Packer for ZipPacker<W>
+where
+    W: Write + Seek,
+{
+    fn add_directory(&mut self, path: &Path) -> Result<()> {
+        debug!("Adding directory `{}` to archive", path.display());
+
+        let name = match self.entry_name(path) {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+        let metadata = fs::metadata(path)
+            .map_err(ZipError::Io)
+            .map_err(Error::ZipPacker)?;
+
+        self.zip
+            .add_directory(name, self.entry_options(&metadata))
+            .map_err(Error::ZipPacker)?;
+
+        Ok(())
+    }
+
+    fn add_symlink(&mut self, path: &Path) -> Result<()> {
+        debug!("Adding symlink `{}` to archive", path.display());
+
+        let name = match self.entry_name(path) {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+        let metadata = fs::symlink_metadata(path)
+            .map_err(ZipError::Io)
+            .map_err(Error::ZipPacker)?;
+        let target = fs::read_link(path)
+            .map_err(ZipError::Io)
+            .map_err(Error::ZipPacker)?;
+
+        self.zip
+            .add_symlink(
+                name,
+                target.to_string_lossy(),
+                self.entry_options(&metadata),
+            )
+            .map_err(Error::ZipPacker)?;
+
+        Ok(())
+    }
+
+    fn add_file(&mut self, path: &Path) -> Result<()> {
+        debug!("Adding file `{}` to archive", path.display());
+
+        let name = match self.entry_name(path) {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+        let mut file = File::open(path).map_err(|source| Error::OpenInputFile {
+            source,
+            path: path.display().to_string(),
+        })?;
+        let metadata = file
+            .metadata()
+            .map_err(ZipError::Io)
+            .map_err(Error::ZipPacker)?;
+
+        self.zip
+            .start_file(name, self.entry_options(&metadata))
+            .map_err(Error::ZipPacker)?;
+        io::copy(&mut file, &mut self.zip)
+            .map_err(ZipError::Io)
+            .map_err(Error::ZipPacker)?;
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        debug!("Finishing zip packer");
+
+        self.zip.finish().map_err(Error::ZipPacker)?;
+
+        Ok(())
+    }
+}
+
+pub struct ZipUnpacker<R>
+where
+    R: Read + Seek,
+{
+    zip: ZipArchive<R>,
+}
+
+impl<R> ZipUnpacker<R>
+where
+    R: Read + Seek,
+{
+    pub fn new(reader: R) -> Result<Self> {
+        Ok(Self {
+            zip: ZipArchive::new(reader).map_err(Error::ZipUnpacker)?,
+        })
+    }
+}
+
+impl ZipUnpacker<File> {
+    pub fn open(path: &Path) -> Result<Self> {
+        debug!("Opening zip archive `{}`", path.display());
+
+        let file = File::open(path).map_err(|source| Error::OpenArchiveFile {
+            source,
+            path: path.display().to_string(),
+        })?;
+
+        Self::new(file)
+    }
+}
+
+impl<R> Unpacker for ZipUnpacker<R>
+where
+    R: Read + Seek,
+{
+    fn unpack_with(mut self, path: &Path, options: &UnpackOptions) -> Result<()> {
+        debug!("Unpacking zip archive to `{}`", path.display());
+
+        let destination = Destination::create(path, ArchiveFormat::Zip, options)?;
+        let mut budget = Budget::new(&options.limits);
+
+        // metadata of directories is applied last so adding files does not change it
+        let mut directories = Vec::new();
+
+        for index in 0..self.zip.len() {
+            let mut file = self.zip.by_index(index).map_err(Error::ZipUnpacker)?;
+
+            let name = PathBuf::from(file.name());
+            let mode = file.unix_mode();
+            let mtime = unix_time(file.last_modified());
+            let size = file.size();
+
+            budget.add_entry(size)?;
+            check_ratio(&options.limits, size, file.compressed_size())?;
+
+            let output_path = match destination.entry_path(&name)? {
+                Some(output_path) => output_path,
+                None => continue,
+            };
+            // links are checked before an existing file is replaced by them
+            let link_target = if is_symlink(mode) {
+                let mut target = String::new();
+                file.read_to_string(&mut target)
+                    .map_err(ZipError::Io)
+                    .map_err(Error::ZipUnpacker)?;
+
+                destination.check_symlink(&output_path, Path::new(&target))?;
+                Some(target)
+            } else {
+                None
+            };
+
+            let output_path = match destination.prepare(
+                &output_path,
+                file.is_dir(),
+                mtime.and_then(|mtime| mtime.try_into().ok()),
+            )? {
+                Some(output_path) => output_path,
+                None => continue,
+            };
+
+            if file.is_dir() {
+                fs::create_dir_all(&output_path)
+                    .map_err(ZipError::Io)
+                    .map_err(Error::ZipUnpacker)?;
+
+                directories.push((output_path, mode, mtime));
+                continue;
+            }
+
+            if let Some(target) = link_target {
+                create_symlink(&target, &output_path)
+                    .map_err(ZipError::Io)
+                    .map_err(Error::ZipUnpacker)?;
+
+                continue;
+            }
+
+            // the data is cut off at the checked size even if it decompresses to more
+            let mut output = File::create(&output_path)
+                .map_err(ZipError::Io)
+                .map_err(Error::ZipUnpacker)?;
+            io::copy(&mut file.by_ref().take(size), &mut output)
+                .and_then(|_| check_end(&mut file))
+                .map_err(ZipError::Io)
+                .map_err(Error::ZipUnpacker)?;
+
+            let mode = mode.map(|mode| mode & permission_mask(options));
+            set_metadata(&output_path, mode, mtime)
+                .map_err(ZipError::Io)
+                .map_err(Error::ZipUnpacker)?;
+        }
+
+        for (output_path, mode, mtime) in directories.into_iter().rev() {
+            let mode = mode.map(|mode| mode & permission_mask(options));
+            set_metadata(&output_path, mode, mtime)
+                .map_err(ZipError::Io)
+                .map_err(Error::ZipUnpacker)?;
+        }
+
+        Ok(())
+    }
+
+    fn entries_with(mut self, limits: &Limits) -> Result<Vec<Entry>> {
+        debug!("Listing zip archive entries");
+
+        let mut budget = Budget::new(limits);
+
+        (0..self.zip.len())
+            .map(|index| {
+                let mut file = self.zip.by_index(index).map_err(Error::ZipUnpacker)?;
+                let mode = file.unix_mode();
+
+                budget.add_entry(file.size())?;
+                check_ratio(limits, file.size(), file.compressed_size())?;
+
+                let entry_type = if file.is_dir() {
+                    EntryType::Directory
+                } else if is_symlink(mode) {
+                    EntryType::Symlink
+                } else {
+                    EntryType::File
+                };
+
+                let link_target = if entry_type == EntryType::Symlink {
+                    let mut target = String::new();
+                    file.read_to_string(&mut target)
+                        .map_err(ZipError::Io)
+                        .map_err(Error::ZipUnpacker)?;
+                    Some(target.into())
+                } else {
+                    None
+                };
+
+                let default_mode = match entry_type {
+                    EntryType::Directory => 0o755,
+                    _ => 0o644,
+                };
+
+                Ok(Entry {
+                    path: file.name().into(),
+                    entry_type,
+                    size: file.size(),
+                    mode: mode.map_or(default_mode, |mode| mode & 0o7777),
+                    uid: 0,
+                    gid: 0,
+                    user: None,
+                    group: None,
+                    mtime: unix_time(file.last_modified())
+                        .and_then(|mtime| mtime.try_into().ok())
+                        .unwrap_or_default(),
+                    link_target,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Name of the entry for `path` using `/` as separator
+///
+/// Root, prefix and `.` components are dropped because zip entry names are always relative.
+fn entry_name(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            Component::ParentDir => Some("..".into()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Convert a modification time to a MS-DOS timestamp as used by zip
+///
+/// Times not representable by MS-DOS timestamps are replaced with 1980-01-01 00:00.
+fn zip_time(time: Option<SystemTime>) -> DateTime {
+    time.and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .and_then(|duration| duration.as_secs().try_into().ok())
+        .and_then(|secs| NaiveDateTime::from_timestamp_opt(secs, 0))
+        .and_then(|time| {
+            DateTime::from_date_and_time(
+                time.year().try_into().ok()?,
+                time.month() as u8,
+                time.day() as u8,
+                time.hour() as u8,
+                time.minute() as u8,
+                time.second() as u8,
+            )
+            .ok()
+        })
+        .unwrap_or_default()
+}
+
+/// Convert a MS-DOS timestamp to seconds since the unix epoch
+fn unix_time(time: DateTime) -> Option<i64> {
+    NaiveDate::from_ymd_opt(time.year().into(), time.month().into(), time.day().into())
+        .and_then(|date| {
+            date.and_hms_opt(
+                time.hour().into(),
+                time.minute().into(),
+                time.second().into(),
+            )
+        })
+        .map(|time| time.timestamp())
+}
+
+/// Whether a unix mode describes a symlink
+fn is_symlink(mode: Option<u32>) -> bool {
+    matches!(mode, Some(mode) if mode & S_IFMT == S_IFLNK)
+}
+
+/// Check an entry was read completely, which makes the zip crate verify its CRC
+///
+/// An entry decompressing to more than the size in its header is refused.
+fn check_end<R>(file: &mut R) -> io::Result<()>
+where
+    R: Read,
+{
+    match file.read(&mut [0]) {
+        Ok(0) => Ok(()),
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "entry is larger than its size",
+        )),
+        Err(error) => Err(error),
+    }
+}
+
+/// Mode bits of entries restored when unpacking
+///
+/// Setuid, setgid and sticky bits of untrusted archives are only kept if asked for like tar
+/// does.
+fn permission_mask(options: &UnpackOptions) -> u32 {
+    if options.preserve_permissions {
+        0o7777
+    } else {
+        0o777
+    }
+}
+
+/// Apply the permissions and modification time of an entry to an unpacked file
+fn set_metadata(path: &Path, mode: Option<u32>, mtime: Option<i64>) -> io::Result<()> {
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o7777))?;
+    }
+
+    if let Some(mtime) = mtime {
+        filetime::set_file_mtime(path, FileTime::from_unix_time(mtime, 0))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &str, path: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+#[cfg(not(unix))]
+fn create_symlink(target: &str, path: &Path) -> io::Result<()> {
+    fs::write(path, target)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{error::Error, fs::read_to_string};
+
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    /// File type bits of a regular file
+    #[cfg(unix)]
+    const S_IFREG: u32 = 0o100000;
+
+    /// Create test logger instance
+    fn init() {
+        let _ = env_logger::builder().is_test(true).try_init();
+    }
+
+    /// test packer and unpacker with a directory
+    #[test]
+    fn pack_and_unpack_directory() -> Result<(), Box<dyn Error>> {
+        init();
+        const DIRECTORY_NAME: &str = "directory";
+        const FILE_NAME: &str = "some-file.txt";
+        const FILE_CONTENT: &[u8] = b"some test text";
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+
+        // create directory
+
+        let directory = working_directory.path().join(DIRECTORY_NAME);
+        fs::create_dir(&directory)?;
+
+        // create test file
+
+        let file_path = directory.join(FILE_NAME);
+        fs::write(&file_path, FILE_CONTENT)?;
+
+        // run packer
+
+        let archive_path = working_directory.path().join("archive.zip");
+        let mut packer = ZipPacker::new(File::create(&archive_path)?, CompressionLevel::Auto)?;
+        packer.add_path(&directory)?;
+        packer.finish()?;
+
+        // run unpacker
+
+        let out_dir = tempdir()?;
+
+        let unpacker = ZipUnpacker::open(&archive_path)?;
+        unpacker.unpack(out_dir.path())?;
+
+        // run test
+
+        let unpacked_directory = out_dir.path().join(entry_name(&directory));
+        let content = read_to_string(unpacked_directory.join(FILE_NAME))?;
+        assert_eq!(content.as_bytes(), FILE_CONTENT);
+
+        Ok(())
+    }
+
+    /// test that unix permissions and symlinks survive a round trip
+    #[cfg(unix)]
+    #[test]
+    fn unix_permissions_and_symlinks() -> Result<(), Box<dyn Error>> {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        init();
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+        let directory = working_directory.path().join("directory");
+        fs::create_dir(&directory)?;
+
+        // create executable and symlink
+
+        let script_path = directory.join("script.sh");
+        fs::write(&script_path, b"#!/bin/sh\n")?;
+        fs::set_permissions(&script_path, fs::Permissions::from_mode(0o750))?;
+        symlink("script.sh", directory.join("link"))?;
+
+        // run packer without compression
+
+        let archive_path = working_directory.path().join("archive.zip");
+        let mut packer =
+            ZipPacker::new(File::create(&archive_path)?, CompressionLevel::Numbered(0))?;
+        packer.add_path(&directory)?;
+        packer.finish()?;
+
+        // list entries
+
+        let entries = ZipUnpacker::open(&archive_path)?.entries()?;
+        let link = entries
+            .iter()
+            .find(|entry| entry.path.ends_with("link"))
+            .unwrap();
+        assert_eq!(link.entry_type, EntryType::Symlink);
+        assert_eq!(link.link_target.as_deref(), Some(Path::new("script.sh")));
+
+        // run unpacker
+
+        let out_dir = tempdir()?;
+        ZipUnpacker::open(&archive_path)?.unpack(out_dir.path())?;
+
+        // run tests
+
+        let unpacked_directory = out_dir.path().join(entry_name(&directory));
+
+        let mode = fs::metadata(unpacked_directory.join("script.sh"))?
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o750);
+
+        let target = fs::read_link(unpacked_directory.join("link"))?;
+        assert_eq!(target, Path::new("script.sh"));
+
+        Ok(())
+    }
+
+    /// test unpacker verifies checksums and clears setuid bits of untrusted entries
+    #[cfg(unix)]
+    #[test]
+    fn unpack_untrusted_entries() -> Result<(), Box<dyn Error>> {
+        use std::os::unix::fs::PermissionsExt;
+
+        init();
+        const FILE_CONTENT: &[u8] = b"some test text";
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+        let archive_path = working_directory.path().join("archive.zip");
+
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        let mut zip = ZipWriter::new(File::create(&archive_path)?);
+        zip.start_file("setuid", stored.unix_permissions(0o755))?;
+        zip.write_all(FILE_CONTENT)?;
+        zip.finish()?;
+
+        // the zip crate only writes permission bits, set the setuid bit in the external
+        // attributes of the central directory
+        let mut archive = fs::read(&archive_path)?;
+        let attributes = ((S_IFREG | 0o755) << 16).to_le_bytes();
+        let offset = archive
+            .windows(attributes.len())
+            .rposition(|window| window == attributes)
+            .unwrap();
+        archive[offset..offset + attributes.len()]
+            .copy_from_slice(&((S_IFREG | 0o4755) << 16).to_le_bytes());
+        fs::write(&archive_path, archive)?;
+
+        // run unpacker with and without preserving permissions
+
+        for (preserve_permissions, expected) in [(false, 0o755), (true, 0o4755)] {
+            let out_dir = tempdir()?;
+            let options = UnpackOptions {
+                preserve_permissions,
+                ..UnpackOptions::default()
+            };
+            ZipUnpacker::open(&archive_path)?.unpack_with(out_dir.path(), &options)?;
+
+            let mode = fs::metadata(out_dir.path().join("setuid"))?
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o7777, expected);
+        }
+
+        // corrupt the stored data without updating its checksum
+
+        let mut archive = fs::read(&archive_path)?;
+        let offset = archive
+            .windows(FILE_CONTENT.len())
+            .position(|window| window == FILE_CONTENT)
+            .unwrap();
+        archive[offset] ^= 0xff;
+        fs::write(&archive_path, archive)?;
+
+        let out_dir = tempdir()?;
+        let result = ZipUnpacker::open(&archive_path)?.unpack(out_dir.path());
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    /// test unpacker refuses entries and symlinks leading outside of the destination
+    #[test]
+    fn refuse_unsafe_paths() -> Result<(), Box<dyn Error>> {
+        init();
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+        let out_dir = working_directory.path().join("out");
+
+        // create malicious archives
+
+        let parent_path = working_directory.path().join("parent.zip");
+        let mut zip = ZipWriter::new(File::create(&parent_path)?);
+        zip.start_file("../parent.txt", FileOptions::default())?;
+        zip.write_all(b"malicious")?;
+        zip.finish()?;
+
+        let symlink_path = working_directory.path().join("symlink.zip");
+        let mut zip = ZipWriter::new(File::create(&symlink_path)?);
+        zip.add_symlink("link", "../..", FileOptions::default())?;
+        zip.finish()?;
+
+        // run tests
+
+        let result = ZipUnpacker::open(&parent_path)?.unpack(&out_dir);
+        assert!(
+            matches!(result, Err(crate::error::Error::UnsafeEntryPath(_))),
+            "{result:?}"
+        );
+        assert!(!working_directory.path().join("parent.txt").exists());
+
+        let result = ZipUnpacker::open(&symlink_path)?.unpack(&out_dir);
+        assert!(
+            matches!(result, Err(crate::error::Error::UnsafeLinkTarget { .. })),
+            "{result:?}"
+        );
+        assert!(fs::symlink_metadata(out_dir.join("link")).is_err());
+
+        Ok(())
+    }
+
+    /// test that more than 65535 entries are written with a Zip64 end of central directory
+    #[test]
+    fn zip64_entry_count() -> Result<(), Box<dyn Error>> {
+        init();
+        const ENTRY_COUNT: usize = u16::MAX as usize + 2;
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+        let directory = working_directory.path().join("directory");
+        fs::create_dir(&directory)?;
+
+        for index in 0..ENTRY_COUNT - 1 {
+            File::create(directory.join(index.to_string()))?;
+        }
+
+        // run packer
+
+        let archive_path = working_directory.path().join("archive.zip");
+        let mut packer = ZipPacker::new(File::create(&archive_path)?, CompressionLevel::Auto)?;
+        packer.add_path(&directory)?;
+        packer.finish()?;
+
+        // run test
+
+        let entries = ZipUnpacker::open(&archive_path)?.entries()?;
+        assert_eq!(entries.len(), ENTRY_COUNT);
+
+        Ok(())
+    }
+}
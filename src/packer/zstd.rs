@@ -0,0 +1,297 @@
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{self, BufReader, Read, Write},
+    path::Path,
+};
+
+use log::debug;
+use zstd::stream::{read::Decoder, write::Encoder};
+
+use crate::{
+    error::{Error, Result},
+    extract::{Limits, UnpackOptions},
+    info::Entry,
+    walk::WalkOptions,
+    ArchiveFormat, CompressionLevel,
+};
+
+use super::{modified_time, stream_entry, stream_name, unpack_stream, Packer, Unpacker};
+
+/// Window size used by `zstd --long` without an explicit value
+pub const DEFAULT_LONG_WINDOW_LOG: u32 = 27;
+
+/// Smallest window size supported by zstd
+pub const MIN_WINDOW_LOG: u32 = 10;
+
+/// Largest window size supported by zstd
+///
+/// The decoder refuses frames with a window above 2^27 by default, archives written in long
+/// distance mode need this as limit.
+pub const MAX_WINDOW_LOG: u32 = if cfg!(target_pointer_width = "64") {
+    31
+} else {
+    30
+};
+
+/// Map a compression level to a zstd level
+pub(crate) fn compression_level(level: CompressionLevel) -> i32 {
+    match level {
+        CompressionLevel::Auto => zstd::DEFAULT_COMPRESSION_LEVEL,
+        CompressionLevel::Numbered(n) => n,
+    }
+}
+
+/// Create an encoder writing a checksummed zstd frame
+///
+/// Long distance matching is enabled with a window of `2^window_log` bytes if
+/// `long_window_log` is set.
+pub(crate) fn frame_encoder<W>(
+    writer: W,
+    level: i32,
+    long_window_log: Option<u32>,
+) -> io::Result<Encoder<'static, W>>
+where
+    W: Write,
+{
+    let mut encoder = Encoder::new(writer, level)?;
+    encoder.include_checksum(true)?;
+
+    if let Some(window_log) = long_window_log {
+        encoder.long_distance_matching(true)?;
+        encoder.window_log(window_log)?;
+    }
+
+    Ok(encoder)
+}
+
+pub struct ZstdPacker<W>
+where
+    W: Write,
+{
+    writer: W,
+    level: i32,
+    /// Base 2 logarithm of the window size when long distance matching is enabled
+    long_window_log: Option<u32>,
+}
+
+impl<W> ZstdPacker<W>
+where
+    W: Write,
+{
+    pub fn new(file: W, level: CompressionLevel) -> Result<Self> {
+        Ok(Self {
+            writer: file,
+            level: compression_level(level),
+            long_window_log: None,
+        })
+    }
+
+    /// Enable long distance matching with a window of `2^window_log` bytes
+    ///
+    /// Like `zstd --long` this finds matches far apart in large inputs at the cost of memory
+    /// during compression and decompression.
+    pub fn long_distance_matching(mut self, window_log: Option<u32>) -> Self {
+        self.long_window_log = window_log;
+        self
+    }
+}
+
+impl<W> Packer for ZstdPacker<W>
+where
+    W: Write,
+{
+    fn add_dir(&mut self, path: &Path, _options: &WalkOptions) -> Result<()> {
+        Err(Error::DirectoryNotSupported {
+            format: ArchiveFormat::Zstd,
+            path: path.display().to_string(),
+        })
+    }
+
+    /// Compress a file as a new zstd frame
+    ///
+    /// Adding multiple files results in concatenated zstd frames.
+    fn add_file(&mut self, path: &Path) -> Result<()> {
+        debug!("Adding file `{}` to archive", path.display());
+
+        let mut file = File::open(path).map_err(|source| Error::OpenInputFile {
+            source,
+            path: path.display().to_string(),
+        })?;
+
+        let mut encoder = frame_encoder(&mut self.writer, self.level, self.long_window_log)
+            .map_err(Error::ZstdPacker)?;
+
+        if let Ok(metadata) = file.metadata() {
+            encoder
+                .set_pledged_src_size(Some(metadata.len()))
+                .map_err(Error::ZstdPacker)?;
+        }
+
+        io::copy(&mut file, &mut encoder).map_err(Error::ZstdPacker)?;
+        encoder.finish().map_err(Error::ZstdPacker)?;
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        debug!("Finishing zstd packer");
+
+        self.writer.flush().map_err(Error::ZstdPacker)?;
+
+        Ok(())
+    }
+}
+
+pub struct ZstdUnpacker<R>
+where
+    R: Read,
+{
+    decoder: Decoder<'static, BufReader<R>>,
+    /// Name of the decompressed file, zstd does not store one
+    name: OsString,
+    mtime: Option<u64>,
+}
+
+impl<R> ZstdUnpacker<R>
+where
+    R: Read,
+{
+    pub fn new(reader: R) -> Result<Self> {
+        let mut decoder = Decoder::new(reader).map_err(Error::ZstdUnpacker)?;
+        decoder
+            .window_log_max(MAX_WINDOW_LOG)
+            .map_err(Error::ZstdUnpacker)?;
+
+        Ok(Self {
+            decoder,
+            name: OsString::from("archive"),
+            mtime: None,
+        })
+    }
+}
+
+impl ZstdUnpacker<File> {
+    pub fn open(path: &Path) -> Result<Self> {
+        debug!("Opening zstd archive `{}`", path.display());
+
+        let file = File::open(path).map_err(|source| Error::OpenArchiveFile {
+            source,
+            path: path.display().to_string(),
+        })?;
+        let mtime = modified_time(&file);
+
+        let mut unpacker = Self::new(file)?;
+        unpacker.name = stream_name(path);
+        unpacker.mtime = mtime;
+
+        Ok(unpacker)
+    }
+}
+
+/// Reading the unpacker yields the decompressed data
+impl<R> Read for ZstdUnpacker<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.decoder.read(buf)
+    }
+}
+
+impl<R> Unpacker for ZstdUnpacker<R>
+where
+    R: Read,
+{
+    fn unpack_with(mut self, path: &Path, options: &UnpackOptions) -> Result<()> {
+        debug!("Unpacking zstd archive to `{}`", path.display());
+
+        unpack_stream(
+            &mut self.decoder,
+            ArchiveFormat::Zstd,
+            &self.name,
+            path,
+            self.mtime,
+            options,
+        )?;
+
+        Ok(())
+    }
+
+    fn entries_with(mut self, limits: &Limits) -> Result<Vec<Entry>> {
+        debug!("Listing zstd archive entries");
+
+        let entry = stream_entry(
+            &mut self.decoder,
+            ArchiveFormat::Zstd,
+            self.name,
+            self.mtime.unwrap_or_default(),
+            limits,
+        )?;
+
+        Ok(vec![entry])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        error::Error,
+        fs::{self, read_to_string},
+    };
+
+    use pretty_assertions::assert_eq;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    /// Create test logger instance
+    fn init() {
+        let _ = env_logger::builder().is_test(true).try_init();
+    }
+
+    /// test packer and unpacker with a negative level and long distance matching
+    #[test]
+    fn pack_and_unpack_long() -> Result<(), Box<dyn Error>> {
+        init();
+        const FILE_CONTENT: &[u8] = b"some test text";
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+
+        // create test file
+
+        let file_path = working_directory.path().join("some-file.txt");
+        fs::write(&file_path, FILE_CONTENT)?;
+
+        // run packer with long distance matching and two frames
+
+        let archive_path = working_directory.path().join("some-file.txt.zst");
+        let mut packer =
+            ZstdPacker::new(File::create(&archive_path)?, CompressionLevel::Numbered(-5))?
+                .long_distance_matching(Some(30));
+        packer.add_path(&file_path)?;
+        packer.add_path(&file_path)?;
+        packer.finish()?;
+
+        // run test: the archive starts with the zstd magic
+
+        let archive = fs::read(&archive_path)?;
+        assert_eq!(&archive[..4], b"\x28\xB5\x2F\xFD");
+
+        // run unpacker
+
+        let out_dir = tempdir()?;
+
+        let unpacker = ZstdUnpacker::open(&archive_path)?;
+        unpacker.unpack(out_dir.path())?;
+
+        // run test: both frames are decompressed
+
+        let content = read_to_string(out_dir.path().join("some-file.txt"))?;
+        assert_eq!(content.as_bytes(), [FILE_CONTENT, FILE_CONTENT].concat());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,325 @@
+//! Rewriting the names of entries while packing and unpacking
+//!
+//! Names are rewritten in the order tar applies its options: leading components are
+//! stripped, then every [Transform] is applied and finally the prefix is prepended.
+
+use std::{
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
+
+use regex::{Regex, RegexBuilder};
+
+use crate::error::{Error, Result};
+
+/// How to rewrite the names of entries
+#[derive(Debug, Clone, Default)]
+pub struct Rename {
+    /// Leading components removed from every name, entries without more components are skipped
+    pub strip_components: usize,
+    pub transforms: Vec<Transform>,
+    /// Directory every entry is placed in
+    pub prefix: Option<PathBuf>,
+}
+
+impl Rename {
+    /// Rewrite the name of an entry
+    ///
+    /// Returns `None` if nothing is left of the name.
+    pub fn apply(&self, name: &Path) -> Option<PathBuf> {
+        let mut components = name
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .skip(self.strip_components)
+            .peekable();
+
+        components.peek()?;
+
+        let mut name = components.collect::<PathBuf>();
+
+        if !self.transforms.is_empty() {
+            let mut transformed = slash_name(&name);
+
+            for transform in &self.transforms {
+                transformed = transform.apply(&transformed);
+            }
+
+            name = PathBuf::from(transformed);
+        }
+
+        if name.as_os_str().is_empty() {
+            return None;
+        }
+
+        match &self.prefix {
+            Some(prefix) => Some(prefix.join(name)),
+            None => Some(name),
+        }
+    }
+
+    /// Name of the file at `path` in an archive
+    ///
+    /// The leading `/` and everything up to the last `..` component are removed first like GNU
+    /// tar does, so the archive never writes outside of the directory it is unpacked into. The
+    /// same is done again after renaming since a transform or prefix can add them back.
+    pub fn archive_name(&self, path: &Path) -> Option<PathBuf> {
+        let name = self.apply(&relative_name(path))?;
+        let name = relative_name(&name);
+
+        if name.as_os_str().is_empty() {
+            None
+        } else {
+            Some(name)
+        }
+    }
+}
+
+/// Only the normal components after the last `..` component of `path`
+fn relative_name(path: &Path) -> PathBuf {
+    let components = path.components().collect::<Vec<_>>();
+    let start = components
+        .iter()
+        .rposition(|component| *component == Component::ParentDir)
+        .map_or(0, |index| index + 1);
+
+    components[start..]
+        .iter()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect()
+}
+
+/// Components of a name joined with `/` as sed expressions see them
+fn slash_name(name: &Path) -> String {
+    name.components()
+        .map(|component| match component {
+            // joined with the other components the root becomes the leading `/`
+            Component::RootDir => "".into(),
+            component => component.as_os_str().to_string_lossy(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// A sed style `s/regex/replacement/flags` expression
+///
+/// The regex uses extended syntax like `sed -E` and any character can delimit the parts
+/// instead of `/`. The replacement refers to groups with `\1` to `\9` and to the whole match
+/// with `&`. The flag `g` replaces all matches instead of the first and `i` ignores case.
+#[derive(Debug, Clone)]
+pub struct Transform {
+    regex: Regex,
+    /// Replacement in the syntax of [Regex::replace]
+    replacement: String,
+    global: bool,
+}
+
+impl Transform {
+    pub fn apply(&self, name: &str) -> String {
+        if self.global {
+            self.regex.replace_all(name, self.replacement.as_str())
+        } else {
+            self.regex.replace(name, self.replacement.as_str())
+        }
+        .into_owned()
+    }
+}
+
+impl FromStr for Transform {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidTransform(s.to_owned());
+
+        let mut chars = s.strip_prefix('s').ok_or_else(invalid)?.chars();
+        let delimiter = chars.next().filter(|c| *c != '\\').ok_or_else(invalid)?;
+
+        let parts = split_unescaped(chars.as_str(), delimiter);
+        let (pattern, replacement, flags) = match parts.as_slice() {
+            [pattern, replacement, flags] => (pattern, replacement, flags),
+            _ => return Err(invalid()),
+        };
+
+        let mut global = false;
+        let mut case_insensitive = false;
+
+        for flag in flags.chars() {
+            match flag {
+                'g' => global = true,
+                'i' => case_insensitive = true,
+                _ => return Err(invalid()),
+            }
+        }
+
+        let regex = RegexBuilder::new(&regex_pattern(pattern, delimiter))
+            .case_insensitive(case_insensitive)
+            .build()
+            .map_err(|source| Error::TransformRegex {
+                expression: s.to_owned(),
+                source,
+            })?;
+
+        Ok(Self {
+            regex,
+            replacement: replacement_template(replacement, delimiter),
+            global,
+        })
+    }
+}
+
+/// Split `s` at every `delimiter` not escaped by a backslash, escapes are kept
+fn split_unescaped(s: &str, delimiter: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let part = parts.last_mut().expect("parts are never empty");
+                part.push(c);
+                part.extend(chars.next());
+            }
+            c if c == delimiter => parts.push(String::new()),
+            c => parts.last_mut().expect("parts are never empty").push(c),
+        }
+    }
+
+    parts
+}
+
+/// Regex of a sed pattern with an escaped delimiter matching the delimiter literally
+fn regex_pattern(pattern: &str, delimiter: char) -> String {
+    let escaped_delimiter = format!("\\{delimiter}");
+
+    pattern.replace(&escaped_delimiter, &regex::escape(&delimiter.to_string()))
+}
+
+/// Template for [Regex::replace] of a sed replacement
+fn replacement_template(replacement: &str, delimiter: char) -> String {
+    let mut template = String::new();
+    let mut chars = replacement.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(group @ '0'..='9') => template.push_str(&format!("${{{group}}}")),
+                Some('$') => template.push_str("$$"),
+                Some(c) if c == delimiter || c == '&' || c == '\\' => template.push(c),
+                Some(c) => {
+                    template.push('\\');
+                    template.push(c);
+                }
+                None => template.push('\\'),
+            },
+            '&' => template.push_str("${0}"),
+            '$' => template.push_str("$$"),
+            c => template.push(c),
+        }
+    }
+
+    template
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// test stripping, transforming and prefixing names
+    #[test]
+    fn rename() -> Result<()> {
+        let rename = Rename {
+            strip_components: 1,
+            transforms: vec![
+                "s/\\.txt$/.md/".parse()?,
+                "s|^docs/|doc/|".parse()?,
+                "s/o/0/g".parse()?,
+            ],
+            prefix: Some("app-1.0".into()),
+        };
+
+        let cases = [
+            ("project/docs/readme.txt", Some("app-1.0/d0c/readme.md")),
+            ("./project/notes.txt", Some("app-1.0/n0tes.md")),
+            ("project", None),
+            ("project/", None),
+        ];
+
+        for (name, expected) in cases {
+            assert_eq!(
+                rename.apply(Path::new(name)),
+                expected.map(PathBuf::from),
+                "{name}"
+            );
+        }
+
+        let rename = Rename::default();
+        assert_eq!(
+            rename.archive_name(Path::new("/srv/../data/file")),
+            Some(PathBuf::from("data/file"))
+        );
+        assert_eq!(
+            rename.archive_name(Path::new("../../data")),
+            Some(PathBuf::from("data"))
+        );
+        assert_eq!(
+            rename.archive_name(Path::new("./src/../../lib/./mod.rs")),
+            Some(PathBuf::from("lib/mod.rs"))
+        );
+        assert_eq!(rename.archive_name(Path::new("/")), None);
+        assert_eq!(rename.archive_name(Path::new("data/..")), None);
+
+        // renaming can not add leading `/` or `..` components back
+        let rename = Rename {
+            prefix: Some("../x".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            rename.archive_name(Path::new("data/file")),
+            Some(PathBuf::from("x/data/file"))
+        );
+
+        let rename = Rename {
+            transforms: vec![
+                "s|^|../|".parse()?,
+                "s|^\\.\\./secret|/etc/passwd|".parse()?,
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            rename.archive_name(Path::new("data/file")),
+            Some(PathBuf::from("data/file"))
+        );
+        assert_eq!(
+            rename.archive_name(Path::new("secret")),
+            Some(PathBuf::from("etc/passwd"))
+        );
+
+        Ok(())
+    }
+
+    /// test sed syntax of transforms
+    #[test]
+    fn transforms() -> Result<()> {
+        let cases = [
+            ("s/a/b/", "banana", "bbnana"),
+            ("s/a/b/g", "banana", "bbnbnb"),
+            ("s/A/b/gi", "banana", "bbnbnb"),
+            (r"s/(b)(a)/\2\1/", "banana", "abnana"),
+            ("s/nan/[&]/", "banana", "ba[nan]a"),
+            (r"s/nan/\&/", "banana", "ba&a"),
+            (r"s|/|\||g", "a/b/c", "a|b|c"),
+            (r"s/\//-/g", "a/b/c", "a-b-c"),
+            ("s/a/$1/", "banana", "b$1nana"),
+        ];
+
+        for (expression, name, expected) in cases {
+            let transform = expression.parse::<Transform>()?;
+            assert_eq!(transform.apply(name), expected, "{expression}");
+        }
+
+        for expression in ["", "x/a/b/", "s/a/b", "s/a/b/q", "s/(/b/", "s\\a\\b\\"] {
+            assert!(expression.parse::<Transform>().is_err(), "{expression}");
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+//! Normalized metadata for byte identical archives of identical trees
+//!
+//! Packers given a [Reproducible] store the same owner, permissions and times for a file no
+//! matter who checked it out when, like `tar --sort=name --owner=0 --group=0 --numeric-owner
+//! --mtime=$SOURCE_DATE_EPOCH --clamp-mtime` does.
+
+use std::{env, fs::Metadata, time::UNIX_EPOCH};
+
+use crate::error::{Error, Result};
+
+/// Environment variable with the time reproducible builds use, see
+/// <https://reproducible-builds.org/specs/source-date-epoch/>
+pub const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
+
+/// How to normalize the metadata of entries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Reproducible {
+    /// Modification times later than this are clamped to it, in seconds since the unix epoch
+    pub mtime: u64,
+}
+
+impl Reproducible {
+    /// Clamp modification times to `SOURCE_DATE_EPOCH` or to the unix epoch if it is not set
+    pub fn from_env() -> Result<Self> {
+        match env::var(SOURCE_DATE_EPOCH) {
+            Ok(value) => value
+                .trim()
+                .parse()
+                .map(|mtime| Self { mtime })
+                .map_err(|_| Error::InvalidSourceDateEpoch(value)),
+            Err(env::VarError::NotPresent) => Ok(Self::default()),
+            Err(env::VarError::NotUnicode(value)) => Err(Error::InvalidSourceDateEpoch(
+                value.to_string_lossy().into_owned(),
+            )),
+        }
+    }
+
+    /// Modification time of an entry in seconds since the unix epoch
+    pub fn mtime(&self, metadata: &Metadata) -> u64 {
+        metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default()
+            .min(self.mtime)
+    }
+
+    /// Permission bits of an entry
+    ///
+    /// Directories and files executable by their owner get `0755`, symlinks `0777` and all
+    /// other files `0644`.
+    pub fn mode(&self, metadata: &Metadata) -> u32 {
+        if metadata.file_type().is_symlink() {
+            0o777
+        } else if metadata.is_dir() || is_executable(metadata) {
+            0o755
+        } else {
+            0o644
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o100 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &Metadata) -> bool {
+    false
+}
@@ -0,0 +1,333 @@
+//! Directory traversal shared by all packers
+//!
+//! [Packer::add_dir](crate::packer::Packer::add_dir) walks directories with [walk] so every
+//! format adds the same entries in the same order and only decides how to store each kind of
+//! file.
+
+use std::{
+    fs::FileType,
+    path::{Path, PathBuf},
+};
+
+use log::{debug, warn};
+use walkdir::WalkDir;
+
+use crate::{
+    error::Result,
+    filter::{self, Filter, Pattern},
+};
+
+/// Options for walking the directories added to an archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalkOptions {
+    /// Add the targets of symlinks instead of the links
+    pub follow_symlinks: bool,
+    /// Do not descend into directories on other file systems
+    pub same_file_system: bool,
+    /// Levels to descend below the added directory, `Some(0)` only adds the directory itself
+    pub max_depth: Option<usize>,
+    /// Skip fifos, sockets and device files instead of adding them
+    pub skip_special: bool,
+    /// Walk the entries of each directory sorted by file name
+    ///
+    /// Otherwise the order depends on the file system.
+    pub sorted: bool,
+    /// Paths to leave out
+    pub filter: Filter,
+}
+
+/// Symlinks are stored as links in a deterministic order like `tar --sort=name` does
+impl Default for WalkOptions {
+    fn default() -> Self {
+        Self {
+            follow_symlinks: false,
+            same_file_system: false,
+            max_depth: None,
+            skip_special: true,
+            sorted: true,
+            filter: Filter::default(),
+        }
+    }
+}
+
+/// Kind of a file found while walking a directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Directory,
+    File,
+    Symlink,
+    /// Fifo, socket or device file
+    Special,
+}
+
+impl From<FileType> for FileKind {
+    fn from(file_type: FileType) -> Self {
+        if file_type.is_dir() {
+            Self::Directory
+        } else if file_type.is_file() {
+            Self::File
+        } else if file_type.is_symlink() {
+            Self::Symlink
+        } else {
+            Self::Special
+        }
+    }
+}
+
+/// A file found while walking a directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalkEntry {
+    pub path: PathBuf,
+    pub kind: FileKind,
+    /// Levels below the walked directory, the directory itself has depth 0
+    pub depth: usize,
+}
+
+/// Walk the directory `path` and everything below it
+///
+/// The directory itself is the first entry and every directory comes before its contents.
+/// Excluded directories are not descended into.
+pub fn walk<'a>(path: &'a Path, options: &'a WalkOptions) -> Walk<'a> {
+    let mut walk_dir = WalkDir::new(path)
+        .follow_links(options.follow_symlinks)
+        .same_file_system(options.same_file_system);
+
+    if let Some(max_depth) = options.max_depth {
+        walk_dir = walk_dir.max_depth(max_depth);
+    }
+
+    if options.sorted {
+        walk_dir = walk_dir.sort_by_file_name();
+    }
+
+    Walk {
+        root: path,
+        options,
+        entries: walk_dir.into_iter(),
+        ignored: Vec::new(),
+    }
+}
+
+/// Iterator over the entries of a walked directory, see [walk]
+pub struct Walk<'a> {
+    root: &'a Path,
+    options: &'a WalkOptions,
+    entries: walkdir::IntoIter,
+    /// Depth, path and patterns of the directories above the next entry with ignore files
+    ignored: Vec<(usize, PathBuf, Vec<Pattern>)>,
+}
+
+impl<'a> Walk<'a> {
+    /// Whether the patterns of the command line or the nearest ignore file exclude a path
+    fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        let relative_path = path.strip_prefix(self.root).unwrap_or(path);
+
+        self.options
+            .filter
+            .is_excluded(relative_path, is_dir)
+            .or_else(|| {
+                // deeper ignore files override those of their parents
+                self.ignored
+                    .iter()
+                    .rev()
+                    .find_map(|(_, directory, patterns)| {
+                        let relative_path = path.strip_prefix(directory).unwrap_or(path);
+                        filter::is_excluded(patterns, relative_path, is_dir)
+                    })
+            })
+            .unwrap_or(false)
+    }
+}
+
+impl<'a> Iterator for Walk<'a> {
+    type Item = Result<WalkEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entry = match self.entries.next()? {
+                Ok(entry) => entry,
+                Err(error) => return Some(Err(error.into())),
+            };
+
+            // the walked directory is always descended into, even if it is a symlink
+            let kind = if entry.depth() == 0 && entry.path().is_dir() {
+                FileKind::Directory
+            } else {
+                entry.file_type().into()
+            };
+
+            // ignore files only apply to the directory containing them and below
+            while matches!(self.ignored.last(), Some((depth, _, _)) if *depth >= entry.depth()) {
+                self.ignored.pop();
+            }
+
+            if entry.depth() > 0 && self.is_excluded(entry.path(), kind == FileKind::Directory) {
+                debug!("Excluding `{}`", entry.path().display());
+
+                if kind == FileKind::Directory {
+                    self.entries.skip_current_dir();
+                }
+                continue;
+            }
+
+            if kind == FileKind::Special && self.options.skip_special {
+                warn!("Skipping special file `{}`", entry.path().display());
+                continue;
+            }
+
+            if kind == FileKind::Directory {
+                match self.options.filter.read_ignore_files(entry.path()) {
+                    Ok(patterns) if patterns.is_empty() => {}
+                    Ok(patterns) => {
+                        self.ignored
+                            .push((entry.depth(), entry.path().to_owned(), patterns))
+                    }
+                    Err(error) => return Some(Err(error)),
+                }
+            }
+
+            return Some(Ok(WalkEntry {
+                depth: entry.depth(),
+                path: entry.into_path(),
+                kind,
+            }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{error::Error, fs};
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn init() {
+        let _ = env_logger::builder().is_test(true).try_init();
+    }
+
+    /// Names of the walked entries relative to `root`
+    fn walked(root: &Path, options: &WalkOptions) -> Result<Vec<(PathBuf, FileKind)>> {
+        walk(root, options)
+            .map(|entry| {
+                entry.map(|entry| {
+                    let name = entry.path.strip_prefix(root).unwrap().to_owned();
+                    (name, entry.kind)
+                })
+            })
+            .collect()
+    }
+
+    /// test order, depth and symlinks of walked entries
+    #[test]
+    fn walk_directory() -> Result<(), Box<dyn Error>> {
+        init();
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+        let root = working_directory.path().join("root");
+
+        fs::create_dir_all(root.join("b/inner"))?;
+        fs::write(root.join("c.txt"), "c")?;
+        fs::write(root.join("a.txt"), "a")?;
+        fs::write(root.join("b/inner/file.txt"), "file")?;
+
+        // run tests
+
+        let entries = walked(&root, &WalkOptions::default())?;
+        assert_eq!(
+            entries,
+            [
+                ("".into(), FileKind::Directory),
+                ("a.txt".into(), FileKind::File),
+                ("b".into(), FileKind::Directory),
+                ("b/inner".into(), FileKind::Directory),
+                ("b/inner/file.txt".into(), FileKind::File),
+                ("c.txt".into(), FileKind::File),
+            ]
+        );
+
+        let options = WalkOptions {
+            max_depth: Some(1),
+            ..WalkOptions::default()
+        };
+        let entries = walked(&root, &options)?;
+        assert_eq!(entries.len(), 4);
+        assert!(entries
+            .iter()
+            .all(|(name, _)| name.components().count() <= 1));
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("b", root.join("link"))?;
+
+            let entries = walked(&root, &WalkOptions::default())?;
+            assert!(entries.contains(&("link".into(), FileKind::Symlink)));
+            assert!(!entries.contains(&("link/inner".into(), FileKind::Directory)));
+
+            let options = WalkOptions {
+                follow_symlinks: true,
+                ..WalkOptions::default()
+            };
+            let entries = walked(&root, &options)?;
+            assert!(entries.contains(&("link".into(), FileKind::Directory)));
+            assert!(entries.contains(&("link/inner/file.txt".into(), FileKind::File)));
+        }
+
+        Ok(())
+    }
+
+    /// test excludes, includes and ignore files
+    #[test]
+    fn walk_filtered() -> Result<(), Box<dyn Error>> {
+        init();
+
+        // setup working directory
+
+        let working_directory = tempdir()?;
+        let root = working_directory.path().join("root");
+
+        fs::create_dir_all(root.join("target/debug"))?;
+        fs::create_dir_all(root.join("web/node_modules/package"))?;
+        fs::write(root.join("target/debug/brick"), "binary")?;
+        fs::write(root.join("web/node_modules/package/index.js"), "js")?;
+        fs::write(root.join("web/index.js"), "js")?;
+        fs::write(root.join("web/debug.log"), "log")?;
+        fs::write(root.join("web/keep.log"), "log")?;
+        fs::write(root.join(".gitignore"), "*.log\n")?;
+        fs::write(root.join("web/.brickignore"), "node_modules/\n!keep.log\n")?;
+
+        // run tests
+
+        let mut options = WalkOptions::default();
+        options.filter.exclude("/target");
+
+        let entries = walked(&root, &options)?;
+        let names = entries.iter().map(|(name, _)| name).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                Path::new(""),
+                Path::new(".gitignore"),
+                Path::new("web"),
+                Path::new("web/.brickignore"),
+                Path::new("web/debug.log"),
+                Path::new("web/index.js"),
+                Path::new("web/keep.log"),
+            ]
+        );
+
+        options.filter.ignore_files.push(filter::GITIGNORE.into());
+        options.filter.include("target/");
+
+        let entries = walked(&root, &options)?;
+        let names = entries.iter().map(|(name, _)| name).collect::<Vec<_>>();
+        assert!(names.contains(&&PathBuf::from("target/debug/brick")));
+        assert!(!names.contains(&&PathBuf::from("web/debug.log")));
+        assert!(names.contains(&&PathBuf::from("web/keep.log")));
+
+        Ok(())
+    }
+}
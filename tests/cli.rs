@@ -1,10 +1,48 @@
+use std::{
+    fs::{self, File},
+    path::Path,
+};
+
 use assert_cmd::Command;
+use flate2::read::GzDecoder;
 use predicates::prelude::*;
+use pretty_assertions::assert_eq;
+use serde_json::Value;
+use tar::Archive;
+use tempfile::{tempdir, TempDir};
+
+const DIRECTORY_NAME: &str = "directory";
+const FILE_NAME: &str = "some-file.txt";
+const FILE_CONTENT: &str = "some test text";
 
 fn command() -> Command {
     Command::cargo_bin("brick").unwrap()
 }
 
+/// Create a working directory containing a directory with a single file
+fn setup_directory() -> TempDir {
+    let working_directory = tempdir().unwrap();
+    let directory = working_directory.path().join(DIRECTORY_NAME);
+    fs::create_dir(&directory).unwrap();
+    fs::write(directory.join(FILE_NAME), FILE_CONTENT).unwrap();
+
+    working_directory
+}
+
+/// Create a working directory containing `archive.tar` packed by brick
+fn setup_tar_archive() -> TempDir {
+    let working_directory = setup_directory();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["pack", "-f", "tar", DIRECTORY_NAME, "--", "archive.tar"])
+        .assert()
+        .append_context("pack", "tar")
+        .success();
+
+    working_directory
+}
+
 #[test]
 fn command_exists() {
     command();
@@ -32,3 +70,1229 @@ fn pack_without_args_displays_help() {
         .code(predicate::eq(2))
         .stderr(predicate::str::contains("USAGE:"));
 }
+
+#[test]
+fn unpack_without_args_displays_help() {
+    command()
+        .arg("unpack")
+        .assert()
+        .append_context("unpack", "no args")
+        .failure()
+        .code(predicate::eq(2))
+        .stderr(predicate::str::contains("USAGE:"));
+}
+
+#[test]
+fn unpack_unknown_format_fails() {
+    let working_directory = tempdir().unwrap();
+    fs::write(working_directory.path().join("archive.unknown"), b"").unwrap();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["unpack", "archive.unknown"])
+        .assert()
+        .append_context("unpack", "unknown format")
+        .failure();
+}
+
+#[test]
+fn unpack_detects_format_without_extension() {
+    let working_directory = setup_tar_archive();
+    fs::rename(
+        working_directory.path().join("archive.tar"),
+        working_directory.path().join("download"),
+    )
+    .unwrap();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["unpack", "download", "-o", "out"])
+        .assert()
+        .append_context("unpack", "no extension")
+        .success();
+
+    let content = fs::read_to_string(
+        working_directory
+            .path()
+            .join("out")
+            .join(DIRECTORY_NAME)
+            .join(FILE_NAME),
+    )
+    .unwrap();
+    assert_eq!(content, FILE_CONTENT);
+}
+
+#[test]
+fn info_warns_on_wrong_extension() {
+    let working_directory = setup_tar_archive();
+    fs::rename(
+        working_directory.path().join("archive.tar"),
+        working_directory.path().join("archive.zip"),
+    )
+    .unwrap();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["info", "archive.zip"])
+        .assert()
+        .append_context("info", "wrong extension")
+        .success()
+        .stdout(predicate::str::contains("Format: tar"))
+        .stderr(predicate::str::contains(
+            "is named like a zip archive but contains tar",
+        ));
+}
+
+#[test]
+fn info_warns_on_wrong_inner_extension() {
+    let working_directory = setup_tar_archive();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["pack", "-f", "gzip", "archive.tar", "--", "archive.zip.gz"])
+        .assert()
+        .append_context("pack", "gzip")
+        .success();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["info", "archive.zip.gz"])
+        .assert()
+        .append_context("info", "wrong inner extension")
+        .failure()
+        .stderr(predicate::str::contains(
+            "is named like a zip archive inside gzip but contains tar",
+        ));
+}
+
+#[test]
+fn pack_and_unpack_tar() {
+    let working_directory = setup_directory();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["pack", "-f", "tar", DIRECTORY_NAME, "--", "archive.tar"])
+        .assert()
+        .append_context("pack", "tar")
+        .success();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["unpack", "archive.tar", "-o", "out"])
+        .assert()
+        .append_context("unpack", "tar")
+        .success();
+
+    let content = fs::read_to_string(
+        working_directory
+            .path()
+            .join("out")
+            .join(DIRECTORY_NAME)
+            .join(FILE_NAME),
+    )
+    .unwrap();
+    assert_eq!(content, FILE_CONTENT);
+}
+
+#[cfg(unix)]
+#[test]
+fn pack_walk_options() {
+    let working_directory = setup_directory();
+    let directory = working_directory.path().join(DIRECTORY_NAME);
+    fs::create_dir(directory.join("inner")).unwrap();
+    fs::write(directory.join("inner").join(FILE_NAME), FILE_CONTENT).unwrap();
+    std::os::unix::fs::symlink(FILE_NAME, directory.join("link")).unwrap();
+
+    for (args, expected) in [
+        (vec![], "link -> some-file.txt"),
+        (vec!["--follow-symlinks"], "Uncompressed size: 42 bytes"),
+        (vec!["--max-depth", "1"], "Entries: 4"),
+    ] {
+        command()
+            .current_dir(&working_directory)
+            .args(["pack", "--overwrite", "-f", "tar"])
+            .args(&args)
+            .args([DIRECTORY_NAME, "--", "archive.tar"])
+            .assert()
+            .append_context("pack", args.join(" "))
+            .success();
+
+        command()
+            .current_dir(&working_directory)
+            .args(["info", "archive.tar"])
+            .assert()
+            .append_context("info", args.join(" "))
+            .success()
+            .stdout(predicate::str::contains(expected));
+    }
+}
+
+#[test]
+fn pack_excludes() {
+    let working_directory = setup_directory();
+    let directory = working_directory.path().join(DIRECTORY_NAME);
+    fs::create_dir_all(directory.join(".git")).unwrap();
+    fs::create_dir_all(directory.join("target")).unwrap();
+    fs::write(directory.join(".git").join("HEAD"), "ref").unwrap();
+    fs::write(directory.join("target").join("binary"), "binary").unwrap();
+    fs::write(directory.join("debug.log"), "log").unwrap();
+    fs::write(directory.join(".gitignore"), "target/").unwrap();
+    fs::write(working_directory.path().join("patterns"), "*.txt").unwrap();
+
+    for (args, included, excluded) in [
+        (
+            vec!["--exclude", "*.log", "--exclude-vcs"],
+            "directory/some-file.txt",
+            "directory/debug.log",
+        ),
+        (vec!["--exclude-vcs"], "directory/debug.log", ".git"),
+        (vec!["--gitignore"], "directory/.git/HEAD", "target"),
+        (
+            vec!["--exclude", "*", "--include", "*.log"],
+            "directory/debug.log",
+            "some-file.txt",
+        ),
+        (
+            vec!["--exclude-from", "patterns"],
+            "debug.log",
+            "some-file.txt",
+        ),
+    ] {
+        command()
+            .current_dir(&working_directory)
+            .args(["pack", "--overwrite", "-f", "tar"])
+            .args(&args)
+            .args([DIRECTORY_NAME, "--", "archive.tar"])
+            .assert()
+            .append_context("pack", args.join(" "))
+            .success();
+
+        command()
+            .current_dir(&working_directory)
+            .args(["info", "archive.tar"])
+            .assert()
+            .append_context("info", args.join(" "))
+            .success()
+            .stdout(predicate::str::contains(included))
+            .stdout(predicate::str::contains(excluded).not());
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn pack_files_from() {
+    let working_directory = setup_directory();
+    let directory = working_directory.path().join(DIRECTORY_NAME);
+    fs::write(directory.join("with\nnewline.txt"), FILE_CONTENT).unwrap();
+    fs::write(
+        working_directory.path().join("list"),
+        "directory/some-file.txt\r\n\n",
+    )
+    .unwrap();
+
+    command()
+        .current_dir(&working_directory)
+        .args([
+            "pack",
+            "-f",
+            "tar",
+            "--files-from",
+            "list",
+            "--",
+            "list.tar",
+        ])
+        .assert()
+        .append_context("pack", "newline separated")
+        .success();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["pack", "-f", "tar", "-T", "-", "--null", "--", "null.tar"])
+        .write_stdin("directory/some-file.txt\0directory/with\nnewline.txt\0")
+        .assert()
+        .append_context("pack", "null separated")
+        .success();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["info", "list.tar"])
+        .assert()
+        .append_context("info", "newline separated")
+        .success()
+        .stdout(predicate::str::contains("directory/some-file.txt"))
+        .stdout(predicate::str::contains("Entries: 1"));
+
+    command()
+        .current_dir(&working_directory)
+        .args(["info", "null.tar"])
+        .assert()
+        .append_context("info", "null separated")
+        .success()
+        .stdout(predicate::str::contains("with\nnewline.txt"))
+        .stdout(predicate::str::contains("Entries: 2"));
+
+    command()
+        .current_dir(&working_directory)
+        .args(["pack", "-f", "gzip", "-T", "-", "-", "--", "stdin.gz"])
+        .write_stdin("directory/some-file.txt")
+        .assert()
+        .append_context("pack", "stdin used twice")
+        .failure()
+        .stderr(predicate::str::contains("stdin can not hold both"));
+}
+
+#[test]
+fn pack_and_unpack_renamed() {
+    let working_directory = setup_directory();
+    let absolute_directory = working_directory.path().join(DIRECTORY_NAME);
+
+    command()
+        .current_dir(&working_directory)
+        .arg("pack")
+        .arg(&absolute_directory)
+        .args(["--", "absolute.tar"])
+        .assert()
+        .append_context("pack", "absolute input")
+        .success();
+
+    let name = absolute_directory
+        .strip_prefix("/")
+        .unwrap()
+        .join(FILE_NAME)
+        .display()
+        .to_string();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["info", "absolute.tar"])
+        .assert()
+        .append_context("info", "absolute input")
+        .success()
+        .stdout(predicate::str::contains(format!(" {name}")));
+
+    command()
+        .current_dir(&working_directory)
+        .args([
+            "pack",
+            "--prefix",
+            "app-1.0",
+            "--transform",
+            r"s/\.txt$/.md/",
+        ])
+        .args([DIRECTORY_NAME, "--", "renamed.zip"])
+        .assert()
+        .append_context("pack", "prefix and transform")
+        .success();
+
+    command()
+        .current_dir(&working_directory)
+        .args([
+            "unpack",
+            "renamed.zip",
+            "-o",
+            "out",
+            "--strip-components",
+            "2",
+        ])
+        .assert()
+        .append_context("unpack", "strip components")
+        .success();
+
+    let content = fs::read_to_string(working_directory.path().join("out/some-file.md")).unwrap();
+    assert_eq!(content, FILE_CONTENT);
+
+    command()
+        .current_dir(&working_directory)
+        .args(["pack", "--transform", "s/a/b/x", DIRECTORY_NAME])
+        .args(["--", "invalid.tar"])
+        .assert()
+        .append_context("pack", "invalid transform")
+        .failure()
+        .stderr(predicate::str::contains("Invalid transform"));
+}
+
+#[test]
+fn pack_reproducible() {
+    const SOURCE_DATE_EPOCH: u64 = 1_600_000_000;
+
+    let first_directory = setup_directory();
+    let second_directory = setup_directory();
+
+    // the same tree checked out at another time with another umask
+    let file_path = second_directory.path().join(DIRECTORY_NAME).join(FILE_NAME);
+    let mtime = filetime::FileTime::from_unix_time(SOURCE_DATE_EPOCH as i64 + 3600, 0);
+    filetime::set_file_mtime(&file_path, mtime).unwrap();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(&file_path, fs::Permissions::from_mode(0o600)).unwrap();
+    }
+
+    let pack = |working_directory: &TempDir, input: &str, archive_name: &str| {
+        command()
+            .current_dir(working_directory)
+            .env("SOURCE_DATE_EPOCH", SOURCE_DATE_EPOCH.to_string())
+            .args(["pack", "--reproducible", input, "--", archive_name])
+            .assert()
+            .append_context("pack", archive_name.to_owned())
+            .success();
+
+        fs::read(working_directory.path().join(archive_name)).unwrap()
+    };
+
+    let file_input = Path::new(DIRECTORY_NAME).join(FILE_NAME);
+    let file_input = file_input.to_str().unwrap();
+
+    for (input, archive_name) in [
+        (DIRECTORY_NAME, "archive.tar.gz"),
+        (DIRECTORY_NAME, "archive.zip"),
+        (file_input, "some-file.txt.gz"),
+    ] {
+        assert!(
+            pack(&first_directory, input, archive_name)
+                == pack(&second_directory, input, archive_name),
+            "{archive_name} differs"
+        );
+    }
+
+    let file = File::open(first_directory.path().join("archive.tar.gz")).unwrap();
+    let mut archive = Archive::new(GzDecoder::new(file));
+
+    for entry in archive.entries().unwrap() {
+        let entry = entry.unwrap();
+        let header = entry.header();
+
+        assert_eq!(header.mtime().unwrap(), SOURCE_DATE_EPOCH);
+        assert_eq!((header.uid().unwrap(), header.gid().unwrap()), (0, 0));
+
+        let mode = if header.entry_type().is_dir() {
+            0o755
+        } else {
+            0o644
+        };
+        assert_eq!(header.mode().unwrap(), mode);
+    }
+
+    command()
+        .current_dir(&first_directory)
+        .env("SOURCE_DATE_EPOCH", "yesterday")
+        .args(["pack", "--reproducible", DIRECTORY_NAME])
+        .args(["--", "invalid.tar"])
+        .assert()
+        .append_context("pack", "invalid SOURCE_DATE_EPOCH")
+        .failure()
+        .stderr(predicate::str::contains("Invalid SOURCE_DATE_EPOCH"));
+}
+
+#[test]
+fn info_lists_entries() {
+    let working_directory = setup_tar_archive();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["info", "archive.tar"])
+        .assert()
+        .append_context("info", "text")
+        .success()
+        .stdout(predicate::str::contains("directory/some-file.txt"))
+        .stdout(predicate::str::contains("Entries: 2"))
+        .stdout(predicate::str::contains("Uncompressed size: 14 bytes"));
+}
+
+#[test]
+fn info_json() {
+    let working_directory = setup_tar_archive();
+
+    let output = command()
+        .current_dir(&working_directory)
+        .args(["info", "--output-format", "json", "archive.tar"])
+        .assert()
+        .append_context("info", "json")
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let info: Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(info["format"], "tar");
+    assert_eq!(info["entries"][0]["path"], "directory/");
+    assert_eq!(info["entries"][0]["type"], "directory");
+    assert_eq!(info["entries"][1]["path"], "directory/some-file.txt");
+    assert_eq!(info["entries"][1]["type"], "file");
+    assert_eq!(info["entries"][1]["size"], 14);
+    assert_eq!(info["summary"]["entries"], 2);
+    assert_eq!(info["summary"]["uncompressed_size"], 14);
+}
+
+#[test]
+fn info_ndjson() {
+    let working_directory = setup_tar_archive();
+
+    let output = command()
+        .current_dir(&working_directory)
+        .args(["info", "--output-format", "ndjson", "archive.tar"])
+        .assert()
+        .append_context("info", "ndjson")
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let records = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .collect::<Vec<_>>();
+
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0]["record"], "entry");
+    assert_eq!(records[1]["record"], "entry");
+    assert_eq!(records[1]["path"], "directory/some-file.txt");
+    assert_eq!(records[2]["record"], "summary");
+    assert_eq!(records[2]["entries"], 2);
+}
+
+#[test]
+fn info_csv() {
+    let working_directory = setup_tar_archive();
+
+    let output = command()
+        .current_dir(&working_directory)
+        .args(["info", "--output-format", "csv", "archive.tar"])
+        .assert()
+        .append_context("info", "csv")
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let output = String::from_utf8(output).unwrap();
+    let mut lines = output.lines();
+
+    assert_eq!(
+        lines.next(),
+        Some("path,type,size,mode,uid,gid,user,group,mtime,link_target")
+    );
+    assert!(lines.next().unwrap().starts_with("directory/,directory,0,"));
+    assert!(lines
+        .next()
+        .unwrap()
+        .starts_with("directory/some-file.txt,file,14,"));
+    assert_eq!(lines.next(), Some(""));
+    assert_eq!(
+        lines.next(),
+        Some("entries,uncompressed_size,archive_size,compression_ratio")
+    );
+    assert!(lines.next().unwrap().starts_with("2,14,"));
+    assert_eq!(lines.next(), None);
+}
+
+#[test]
+fn pack_tar_gzip() {
+    let working_directory = setup_directory();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["pack", "-f", "tar", "-f", "gzip", "-c", "9", DIRECTORY_NAME])
+        .assert()
+        .append_context("pack", "tar gzip")
+        .success();
+
+    let archive = File::open(working_directory.path().join("directory.tar.gz")).unwrap();
+    let mut tar = Archive::new(GzDecoder::new(archive));
+
+    let out_dir = tempdir().unwrap();
+    tar.unpack(&out_dir).unwrap();
+
+    let content = fs::read_to_string(out_dir.path().join(DIRECTORY_NAME).join(FILE_NAME)).unwrap();
+    assert_eq!(content, FILE_CONTENT);
+}
+
+#[test]
+fn pack_and_unpack_nested() {
+    let working_directory = setup_directory();
+
+    command()
+        .current_dir(&working_directory)
+        .args([
+            "pack",
+            "-f",
+            "tar",
+            "-f",
+            "gzip",
+            "-f",
+            "xz",
+            DIRECTORY_NAME,
+        ])
+        .assert()
+        .append_context("pack", "tar gzip xz")
+        .success();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["unpack", "directory.tar.gz.xz", "-o", "out"])
+        .assert()
+        .append_context("unpack", "tar gzip xz")
+        .success();
+
+    let content = fs::read_to_string(
+        working_directory
+            .path()
+            .join("out")
+            .join(DIRECTORY_NAME)
+            .join(FILE_NAME),
+    )
+    .unwrap();
+    assert_eq!(content, FILE_CONTENT);
+
+    let output = command()
+        .current_dir(&working_directory)
+        .args(["info", "--output-format", "json", "directory.tar.gz.xz"])
+        .assert()
+        .append_context("info", "tar gzip xz")
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let info: Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(info["format"], "tar");
+    assert_eq!(info["layers"], serde_json::json!(["tar", "gzip", "xz"]));
+    assert_eq!(info["summary"]["entries"], 2);
+}
+
+#[test]
+fn pack_zip_in_gzip_without_intermediate_files() {
+    let working_directory = setup_directory();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["pack", "-f", "zip", "-f", "gzip", DIRECTORY_NAME])
+        .assert()
+        .append_context("pack", "zip gzip")
+        .success();
+
+    let mut files = fs::read_dir(&working_directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect::<Vec<_>>();
+    files.sort();
+    assert_eq!(files, [DIRECTORY_NAME, "directory.zip.gz"]);
+
+    command()
+        .current_dir(&working_directory)
+        .args(["unpack", "directory.zip.gz", "-o", "out"])
+        .assert()
+        .append_context("unpack", "zip gzip")
+        .success();
+
+    let content = fs::read_to_string(
+        working_directory
+            .path()
+            .join("out")
+            .join(DIRECTORY_NAME)
+            .join(FILE_NAME),
+    )
+    .unwrap();
+    assert_eq!(content, FILE_CONTENT);
+}
+
+#[test]
+fn pack_and_unpack_gzip() {
+    let working_directory = setup_directory();
+    let file_path = Path::new(DIRECTORY_NAME).join(FILE_NAME);
+
+    command()
+        .current_dir(&working_directory)
+        .arg("pack")
+        .args(["-f", "gzip"])
+        .arg(&file_path)
+        .args(["--", "archive.gz"])
+        .assert()
+        .append_context("pack", "gzip")
+        .success();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["unpack", "archive.gz", "-o", "out"])
+        .assert()
+        .append_context("unpack", "gzip")
+        .success();
+
+    let content = fs::read_to_string(working_directory.path().join("out").join(FILE_NAME)).unwrap();
+    assert_eq!(content, FILE_CONTENT);
+}
+
+#[test]
+fn pack_and_unpack_lzma_formats() {
+    let working_directory = setup_directory();
+    let file_path = Path::new(DIRECTORY_NAME).join(FILE_NAME);
+
+    for (format, archive_name) in [
+        ("xz", "archive.xz"),
+        ("lzma", "archive.lzma"),
+        ("lzip", "archive.lz"),
+    ] {
+        command()
+            .current_dir(&working_directory)
+            .arg("pack")
+            .args(["-f", format, "--keep"])
+            .arg(&file_path)
+            .args(["--", archive_name])
+            .assert()
+            .append_context("pack", format)
+            .success();
+
+        command()
+            .current_dir(&working_directory)
+            .args(["unpack", archive_name, "-o", format])
+            .assert()
+            .append_context("unpack", format)
+            .success();
+
+        let content =
+            fs::read_to_string(working_directory.path().join(format).join("archive")).unwrap();
+        assert_eq!(content, FILE_CONTENT);
+    }
+}
+
+#[test]
+fn pack_and_unpack_bzip2() {
+    let working_directory = setup_directory();
+    let file_path = Path::new(DIRECTORY_NAME).join(FILE_NAME);
+
+    command()
+        .current_dir(&working_directory)
+        .arg("pack")
+        .args(["-f", "bzip2", "-c", "1"])
+        .arg(&file_path)
+        .args(["--", "archive.bz2"])
+        .assert()
+        .append_context("pack", "bzip2")
+        .success();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["unpack", "archive.bz2", "-o", "out"])
+        .assert()
+        .append_context("unpack", "bzip2")
+        .success();
+
+    let content = fs::read_to_string(working_directory.path().join("out").join("archive")).unwrap();
+    assert_eq!(content, FILE_CONTENT);
+}
+
+#[test]
+fn pack_and_unpack_zstd() {
+    let working_directory = setup_directory();
+    let file_path = Path::new(DIRECTORY_NAME).join(FILE_NAME);
+
+    command()
+        .current_dir(&working_directory)
+        .arg("pack")
+        .args(["-f", "zstd", "-c", "19", "--long=28"])
+        .arg(&file_path)
+        .args(["--", "archive.zst"])
+        .assert()
+        .append_context("pack", "zstd")
+        .success();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["unpack", "archive.zst", "-o", "out"])
+        .assert()
+        .append_context("unpack", "zstd")
+        .success();
+
+    let content = fs::read_to_string(working_directory.path().join("out").join("archive")).unwrap();
+    assert_eq!(content, FILE_CONTENT);
+}
+
+#[test]
+fn pack_rejects_level_out_of_range() {
+    let working_directory = setup_directory();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["pack", "-f", "gzip", "-c", "19", DIRECTORY_NAME])
+        .assert()
+        .append_context("pack", "gzip level 19")
+        .failure()
+        .stderr(predicate::str::contains(
+            "Compression level 19 is not supported by gzip, expected 0 to 9",
+        ));
+}
+
+#[test]
+fn pack_rejects_invalid_chains() {
+    let working_directory = setup_directory();
+    let file_path = Path::new(DIRECTORY_NAME).join(FILE_NAME);
+    let file_path = file_path.to_str().unwrap();
+
+    for (args, message) in [
+        (
+            vec!["-f", "gzip", file_path, file_path],
+            "gzip can only compress a single file, add a container format like tar to pack 2 inputs",
+        ),
+        (
+            vec!["-f", "xz", DIRECTORY_NAME],
+            "xz can only compress a single file, add a container format like tar to pack directory",
+        ),
+        (
+            vec!["-f", "gzip", "-f", "zip", file_path],
+            "zip is a container and can only be used as the first format",
+        ),
+        (
+            vec![DIRECTORY_NAME, "--", "archive.gz.tar"],
+            "tar is a container and can only be used as the first format",
+        ),
+    ] {
+        command()
+            .current_dir(&working_directory)
+            .arg("pack")
+            .args(&args)
+            .assert()
+            .append_context("pack", args.join(" "))
+            .failure()
+            .stderr(predicate::str::contains(message));
+    }
+
+    // nothing is written for an invalid chain
+    let files = fs::read_dir(&working_directory).unwrap().count();
+    assert_eq!(files, 1);
+}
+
+#[test]
+fn pack_and_unpack_single_file() {
+    let working_directory = tempdir().unwrap();
+    let directory = working_directory.path().join("logs");
+    fs::create_dir(&directory).unwrap();
+    fs::write(directory.join("huge.log"), FILE_CONTENT).unwrap();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["pack", "-f", "zstd", "logs/huge.log"])
+        .assert()
+        .append_context("pack", "single file")
+        .success();
+
+    // the compressed file replaces its input like gzip does
+    assert!(!directory.join("huge.log").exists());
+    assert!(directory.join("huge.log.zst").exists());
+    assert!(!working_directory.path().join("huge.log.zst").exists());
+
+    command()
+        .current_dir(&working_directory)
+        .args([
+            "unpack",
+            "--delete-input",
+            "logs/huge.log.zst",
+            "-o",
+            "logs",
+        ])
+        .assert()
+        .append_context("unpack", "single file")
+        .success();
+
+    let content = fs::read_to_string(directory.join("huge.log")).unwrap();
+    assert_eq!(content, FILE_CONTENT);
+    assert!(!directory.join("huge.log.zst").exists());
+}
+
+#[test]
+fn pack_single_file_keep() {
+    let working_directory = setup_directory();
+    let file_path = Path::new(DIRECTORY_NAME).join(FILE_NAME);
+
+    command()
+        .current_dir(&working_directory)
+        .args(["pack", "-f", "xz", "--keep"])
+        .arg(&file_path)
+        .assert()
+        .append_context("pack", "keep")
+        .success();
+
+    let file_path = working_directory.path().join(file_path);
+    assert!(file_path.exists());
+    assert!(file_path.with_extension("txt.xz").exists());
+}
+
+#[test]
+fn delete_input_requires_single_file() {
+    let working_directory = setup_directory();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["pack", "-f", "tar", "--delete-input", DIRECTORY_NAME])
+        .assert()
+        .append_context("pack", "delete input")
+        .failure()
+        .stderr(predicate::str::contains(
+            "Input files can only be deleted for a single compressed file, not for tar archives",
+        ));
+
+    assert!(working_directory.path().join(DIRECTORY_NAME).exists());
+}
+
+#[test]
+fn pack_to_stdout_and_unpack_from_stdin() {
+    let working_directory = setup_directory();
+
+    let archive = command()
+        .current_dir(&working_directory)
+        .args(["pack", "-f", "tar", "-f", "gzip", DIRECTORY_NAME, "--", "-"])
+        .assert()
+        .append_context("pack", "stdout")
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["unpack", "-", "-o", "out"])
+        .write_stdin(archive)
+        .assert()
+        .append_context("unpack", "stdin")
+        .success();
+
+    let content = fs::read_to_string(
+        working_directory
+            .path()
+            .join("out")
+            .join(DIRECTORY_NAME)
+            .join(FILE_NAME),
+    )
+    .unwrap();
+    assert_eq!(content, FILE_CONTENT);
+}
+
+#[test]
+fn compress_stdin_to_stdout() {
+    let compressed = command()
+        .args(["pack", "-f", "xz", "-"])
+        .write_stdin(FILE_CONTENT)
+        .assert()
+        .append_context("pack", "stdin")
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    command()
+        .args(["unpack", "-", "-o", "-"])
+        .write_stdin(compressed)
+        .assert()
+        .append_context("unpack", "stdin to stdout")
+        .success()
+        .stdout(FILE_CONTENT);
+}
+
+#[test]
+fn info_from_stdin() {
+    let compressed = command()
+        .args(["pack", "-f", "xz", "-"])
+        .write_stdin(FILE_CONTENT)
+        .assert()
+        .append_context("pack", "stdin")
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let output = command()
+        .args(["info", "--output-format", "json", "-"])
+        .write_stdin(compressed.clone())
+        .assert()
+        .append_context("info", "stdin")
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let info: Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(info["entries"][0]["path"], "archive");
+    assert_eq!(info["summary"]["archive_size"], compressed.len());
+}
+
+#[test]
+fn pack_to_stdout_requires_format() {
+    let working_directory = setup_directory();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["pack", DIRECTORY_NAME, "--", "-"])
+        .assert()
+        .append_context("pack", "stdout without format")
+        .failure()
+        .stderr(predicate::str::contains(
+            "Writing to stdout requires an explicit format",
+        ));
+}
+
+#[test]
+fn pack_into_output_directory() {
+    let working_directory = setup_directory();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["pack", DIRECTORY_NAME, "--", "out/nested/directory.tar"])
+        .assert()
+        .append_context("pack", "missing directory")
+        .failure()
+        .stderr(predicate::str::contains(
+            "Output directory './out/nested' does not exist, use --parents to create it",
+        ));
+
+    command()
+        .current_dir(&working_directory)
+        .args([
+            "pack",
+            "--parents",
+            DIRECTORY_NAME,
+            "--",
+            "out/nested/directory.tar",
+        ])
+        .assert()
+        .append_context("pack", "parents")
+        .success();
+
+    assert!(working_directory
+        .path()
+        .join("out/nested/directory.tar")
+        .is_file());
+}
+
+#[test]
+fn pack_derives_default_name() {
+    let working_directory = setup_directory();
+
+    command()
+        .current_dir(working_directory.path().join(DIRECTORY_NAME))
+        .args(["pack", "-f", "zip", "."])
+        .assert()
+        .append_context("pack", "current directory")
+        .success();
+
+    assert!(working_directory
+        .path()
+        .join(DIRECTORY_NAME)
+        .join("directory.zip")
+        .is_file());
+}
+
+#[test]
+fn failed_pack_keeps_existing_archive() {
+    let working_directory = tempdir().unwrap();
+    fs::write(working_directory.path().join("archive.gz"), "old archive").unwrap();
+
+    command()
+        .current_dir(&working_directory)
+        .args([
+            "pack",
+            "--overwrite",
+            "missing-file.txt",
+            "--",
+            "archive.gz",
+        ])
+        .assert()
+        .append_context("pack", "missing input")
+        .failure()
+        .stderr(predicate::str::contains(
+            "Could not open input file 'missing-file.txt'",
+        ));
+
+    let content = fs::read_to_string(working_directory.path().join("archive.gz")).unwrap();
+    assert_eq!(content, "old archive");
+    assert_eq!(fs::read_dir(&working_directory).unwrap().count(), 1);
+}
+
+#[test]
+fn pack_refuses_to_overwrite() {
+    let working_directory = setup_directory();
+    let archive_path = working_directory.path().join("directory.zip");
+    fs::write(&archive_path, "release").unwrap();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["pack", "-f", "zip", DIRECTORY_NAME])
+        .assert()
+        .append_context("pack", "existing output")
+        .failure()
+        .code(3)
+        .stderr(predicate::str::contains("already exists"));
+
+    command()
+        .current_dir(&working_directory)
+        .args(["pack", "-f", "zip", "--no-clobber", DIRECTORY_NAME])
+        .assert()
+        .append_context("pack", "no clobber")
+        .failure()
+        .code(3);
+
+    assert_eq!(fs::read_to_string(&archive_path).unwrap(), "release");
+
+    command()
+        .current_dir(&working_directory)
+        .args(["pack", "-f", "zip", "--overwrite", DIRECTORY_NAME])
+        .assert()
+        .append_context("pack", "overwrite")
+        .success();
+
+    assert_ne!(fs::read(&archive_path).unwrap(), b"release");
+}
+
+#[test]
+fn unpack_conflict_policies() {
+    let working_directory = setup_tar_archive();
+    let file_path = working_directory
+        .path()
+        .join(DIRECTORY_NAME)
+        .join(FILE_NAME);
+    let unpack = |flag: &str| {
+        command()
+            .current_dir(&working_directory)
+            .args(["unpack", flag, "archive.tar"])
+            .assert()
+            .append_context("unpack", flag.to_owned())
+            .success();
+    };
+
+    fs::write(&file_path, "changed").unwrap();
+    unpack("--skip-existing");
+    assert_eq!(fs::read_to_string(&file_path).unwrap(), "changed");
+
+    // the existing file was just written and is newer than the entry
+    unpack("--keep-newer");
+    assert_eq!(fs::read_to_string(&file_path).unwrap(), "changed");
+
+    unpack("--rename");
+    unpack("--rename");
+    assert_eq!(fs::read_to_string(&file_path).unwrap(), "changed");
+    for suffix in [".1", ".2"] {
+        let renamed = file_path.with_file_name(format!("{FILE_NAME}{suffix}"));
+        assert_eq!(fs::read_to_string(renamed).unwrap(), FILE_CONTENT);
+    }
+
+    unpack("--backup");
+    assert_eq!(fs::read_to_string(&file_path).unwrap(), FILE_CONTENT);
+    let backup = file_path.with_file_name(format!("{FILE_NAME}~"));
+    assert_eq!(fs::read_to_string(backup).unwrap(), "changed");
+
+    fs::write(&file_path, "changed").unwrap();
+    unpack("--overwrite");
+    assert_eq!(fs::read_to_string(&file_path).unwrap(), FILE_CONTENT);
+
+    command()
+        .current_dir(&working_directory)
+        .args(["unpack", "--rename", "--backup", "archive.tar"])
+        .assert()
+        .append_context("unpack", "multiple policies")
+        .failure()
+        .code(2);
+}
+
+#[test]
+fn unpack_refuses_unsafe_paths() {
+    let working_directory = tempdir().unwrap();
+
+    // tar refuses to write `..` itself so the name is written into the header directly
+    let mut header = tar::Header::new_gnu();
+    let name = b"../evil.txt";
+    header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name);
+    header.set_size(4);
+    header.set_mode(0o644);
+    header.set_cksum();
+
+    let mut tar =
+        tar::Builder::new(File::create(working_directory.path().join("evil.tar")).unwrap());
+    tar.append(&header, &b"evil"[..]).unwrap();
+    tar.into_inner().unwrap();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["unpack", "evil.tar", "-o", "out"])
+        .assert()
+        .append_context("unpack", "unsafe path")
+        .failure()
+        .stderr(predicate::str::contains("Refusing to unpack '../evil.txt'"));
+
+    assert!(!working_directory.path().join("evil.txt").exists());
+
+    command()
+        .current_dir(&working_directory)
+        .args(["unpack", "--unsafe-paths", "evil.tar", "-o", "out"])
+        .assert()
+        .append_context("unpack", "unsafe paths allowed")
+        .success();
+
+    assert!(working_directory.path().join("evil.txt").exists());
+}
+
+#[test]
+fn unpack_stops_at_limits() {
+    let working_directory = setup_tar_archive();
+
+    for args in [
+        ["--max-size", "4"],
+        ["--max-entries", "1"],
+        ["--max-entry-size", "4"],
+        ["--max-layers", "0"],
+    ] {
+        command()
+            .current_dir(&working_directory)
+            .args(["unpack", "archive.tar", "-o", "out"])
+            .args(args)
+            .assert()
+            .append_context("unpack", args.join(" "))
+            .failure()
+            .stderr(predicate::str::contains("Archive exceeds the maximum"));
+
+        command()
+            .current_dir(&working_directory)
+            .args(["info", "archive.tar"])
+            .args(args)
+            .assert()
+            .append_context("info", args.join(" "))
+            .failure()
+            .stderr(predicate::str::contains("Archive exceeds the maximum"));
+    }
+
+    command()
+        .current_dir(&working_directory)
+        .args(["unpack", "--no-limits", "archive.tar", "-o", "out"])
+        .assert()
+        .append_context("unpack", "no limits")
+        .success();
+}
+
+#[test]
+fn pack_and_unpack_zip() {
+    let working_directory = setup_directory();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["pack", "-f", "zip", DIRECTORY_NAME])
+        .assert()
+        .append_context("pack", "zip")
+        .success();
+
+    command()
+        .current_dir(&working_directory)
+        .args(["unpack", "directory.zip", "-o", "out"])
+        .assert()
+        .append_context("unpack", "zip")
+        .success();
+
+    let content = fs::read_to_string(
+        working_directory
+            .path()
+            .join("out")
+            .join(DIRECTORY_NAME)
+            .join(FILE_NAME),
+    )
+    .unwrap();
+    assert_eq!(content, FILE_CONTENT);
+}